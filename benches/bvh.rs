@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use wort::core::{
+    bvh::BvhNode,
+    hit::Hittable,
+    material::DiffuseMethod,
+    ray::Ray,
+    rng::Pcg32,
+    sampler::{independent::IndependentSampler, Sampler},
+};
+use wort::scene::{get_camera, random_scene};
+use wort::settings::RenderSettings;
+
+/// Primary rays through every 4th pixel of the default camera
+fn camera_rays() -> Vec<Ray> {
+    let settings = RenderSettings::default();
+    let camera = get_camera(&settings);
+    (0..settings.image_height)
+        .step_by(4)
+        .flat_map(|j| {
+            let camera = &camera;
+            (0..settings.image_width).step_by(4).map(move |i| {
+                let u = i as f64 / (settings.image_width - 1) as f64;
+                let v = j as f64 / (settings.image_height - 1) as f64;
+                let mut sampler = IndependentSampler::new(settings.seed);
+                sampler.start_pixel_sample(i, j, 0);
+                camera.get_ray(u, v, &mut sampler)
+            })
+        })
+        .collect()
+}
+
+fn trace_all(world: &dyn Hittable, rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|ray| world.hit(ray, 0.001, f64::INFINITY).is_some())
+        .count()
+}
+
+fn random_scene_hits(c: &mut Criterion) {
+    let list = random_scene(&mut DiffuseMethod::Lambert, &mut Pcg32::new(0));
+    let bvh = BvhNode::new(list.clone());
+    let rays = camera_rays();
+
+    let mut group = c.benchmark_group("random_scene");
+    group.bench_function("hittable_list", |b| {
+        b.iter(|| trace_all(black_box(&list), &rays))
+    });
+    group.bench_function("bvh", |b| b.iter(|| trace_all(black_box(&bvh), &rays)));
+    group.finish();
+}
+
+criterion_group!(benches, random_scene_hits);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Equal-time noise comparison of the samplers on the random scene.
+//!
+//! Every sampler traces the samples that fit in the same time budget, the error
+//! is measured against a high sample count reference. Run with `cargo bench --bench samplers`.
+use std::time::{Duration, Instant};
+
+use wort::core::{
+    background::Background, bvh::BvhNode, color::Color, hittable_list::HittableList,
+    material::DiffuseMethod, rng::Pcg32, sampler::SamplerKind,
+};
+use wort::render::Renderer;
+use wort::scene::{get_camera, random_scene};
+use wort::settings::RenderSettings;
+
+const REFERENCE_SAMPLES: usize = 512;
+const SAMPLES_PER_PASS: usize = 4;
+const BUDGET: Duration = Duration::from_secs(2);
+
+const SAMPLERS: [SamplerKind; 5] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+    SamplerKind::BlueNoise,
+];
+
+fn rmse(image: &[Color], reference: &[Color]) -> f64 {
+    let squared_error: f64 = image
+        .iter()
+        .zip(reference)
+        .map(|(pixel, expected)| {
+            let error = *pixel - *expected;
+            error.r * error.r + error.g * error.g + error.b * error.b
+        })
+        .sum();
+    (squared_error / (3 * image.len()) as f64).sqrt()
+}
+
+fn main() {
+    let settings = RenderSettings::new(96, 64, REFERENCE_SAMPLES, 50);
+    let world = random_scene(&mut DiffuseMethod::Lambert, &mut Pcg32::new(settings.seed));
+    let mut renderer = Renderer::new(
+        Box::new(BvhNode::new(world)),
+        HittableList::new(),
+        HittableList::new(),
+        get_camera(&settings),
+        Background::Gradient,
+        RenderSettings {
+            sampler: SamplerKind::Sobol,
+            ..settings
+        },
+    );
+
+    eprintln!("Rendering the {} spp reference...", REFERENCE_SAMPLES);
+    let reference = renderer.trace(0..REFERENCE_SAMPLES, false).pixels();
+
+    // The independent sampler finds how many samples fit the budget, the others
+    // then trace as many so the stratified sampler knows its count up front
+    renderer.settings.sampler = SamplerKind::Independent;
+    let start = Instant::now();
+    let mut samples = 0;
+    while start.elapsed() < BUDGET {
+        renderer.trace(samples..samples + SAMPLES_PER_PASS, false);
+        samples += SAMPLES_PER_PASS;
+    }
+    renderer.settings.samples_per_pixel = samples;
+    // Generates the blue-noise mask outside of the timing
+    SamplerKind::BlueNoise.build(settings.seed, samples);
+
+    println!("{:<12} {:>8} {:>8} {:>10}", "sampler", "samples", "time", "rmse");
+    for sampler in SAMPLERS {
+        renderer.settings.sampler = sampler;
+        let start = Instant::now();
+        let image = renderer.trace(0..samples, false).pixels();
+        println!(
+            "{:<12} {:>8} {:>7.2}s {:>10.5}",
+            sampler,
+            samples,
+            start.elapsed().as_secs_f64(),
+            rmse(&image, &reference)
+        );
+    }
+}
@@ -1,14 +1,68 @@
-use crate::core::material::DiffuseMethod;
-use crate::sinks::Sink;
-use clap::{App, Arg};
-use std::str::FromStr;
+use crate::sinks::{ExrPrecision, Sink};
+use clap::{value_t, App, Arg};
+use std::{path::Path, str::FromStr};
 use strum::VariantNames;
+use wort::core::{background::Background, material::DiffuseMethod, sampler::SamplerKind};
+use wort::render::{
+    film::{Filter, FilterKind},
+    tile::TileOrder,
+};
+use wort::scene::BuiltinScene;
+use wort::settings::{RenderSettings, DEFAULT_ASPECT_RATIO};
+use wort::tonemap::{ToneMapOperator, ToneMapper};
 
 pub struct Args {
     pub output: Sink,
     pub filename: Option<String>,
+    /// File to write the samples taken per pixel to
+    pub heatmap: Option<String>,
     pub diffuse_method: DiffuseMethod,
     pub verbose: bool,
+    pub settings: RenderSettings,
+    pub scene: Option<String>,
+    pub builtin: BuiltinScene,
+    pub background: Option<Background>,
+    pub exr_precision: ExrPrecision,
+    pub tone_mapper: ToneMapper,
+}
+
+/// Parses an aspect ratio given either as a number (`1.5`) or a fraction (`3/2`, `16:9`)
+fn parse_aspect(aspect: &str) -> Result<f64, String> {
+    let err = || format!("Invalid aspect ratio `{}`", aspect);
+    let ratio = match aspect.find(['/', ':']) {
+        Some(idx) => {
+            let w = f64::from_str(aspect[..idx].trim()).map_err(|_| err())?;
+            let h = f64::from_str(aspect[idx + 1..].trim()).map_err(|_| err())?;
+            w / h
+        }
+        None => f64::from_str(aspect.trim()).map_err(|_| err())?,
+    };
+    if ratio.is_finite() && ratio > 0. {
+        Ok(ratio)
+    } else {
+        Err(err())
+    }
+}
+
+fn is_number(value: String) -> Result<(), String> {
+    match f64::from_str(&value) {
+        Ok(n) if n.is_finite() => Ok(()),
+        _ => Err(format!("Expected a number, got `{}`", value)),
+    }
+}
+
+fn is_positive_number(value: String) -> Result<(), String> {
+    match f64::from_str(&value) {
+        Ok(n) if n.is_finite() && n > 0. => Ok(()),
+        _ => Err(format!("Expected a positive number, got `{}`", value)),
+    }
+}
+
+fn is_positive(value: String) -> Result<(), String> {
+    match usize::from_str(&value) {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("Expected a positive integer, got `{}`", value)),
+    }
 }
 
 pub fn parse_arguments() -> Args {
@@ -21,18 +75,53 @@ pub fn parse_arguments() -> Args {
                 .short("o")
                 .long("output")
                 .value_name("OUTPUT")
-                .help("Output - file (PPM) or window")
+                .help("Output - file (PPM), png, window or linear HDR (pfm, hdr, exr)")
                 .takes_value(true)
-                .possible_values(&Sink::VARIANTS),
+                .possible_values(Sink::VARIANTS),
         )
         .arg(
             Arg::with_name("filename")
                 .short("n")
                 .long("filename")
                 .value_name("FILE")
-                .help("Filename - defaults to `image`")
+                .help("Filename - defaults to `image`, its extension (e.g. .png) picks the format")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("exr-precision")
+                .long("exr-precision")
+                .value_name("PRECISION")
+                .help("Channel type of OpenEXR output - defaults to half")
+                .takes_value(true)
+                .possible_values(ExrPrecision::VARIANTS),
+        )
+        .arg(
+            Arg::with_name("tonemap")
+                .short("t")
+                .long("tonemap")
+                .value_name("OPERATOR")
+                .help("Tone mapping operator for display outputs, T cycles it in the window")
+                .takes_value(true)
+                .possible_values(ToneMapOperator::VARIANTS),
+        )
+        .arg(
+            Arg::with_name("exposure")
+                .short("e")
+                .long("exposure")
+                .value_name("STOPS")
+                .help("Exposure adjustment in stops before tone mapping - defaults to 0")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .validator(is_number),
+        )
+        .arg(
+            Arg::with_name("white-point")
+                .long("white-point")
+                .value_name("RADIANCE")
+                .help("Radiance mapped to white by extended-reinhard - defaults to 4")
+                .takes_value(true)
+                .validator(is_number),
+        )
         .arg(
             Arg::with_name("diffuse")
                 .short("d")
@@ -40,14 +129,157 @@ pub fn parse_arguments() -> Args {
                 .value_name("DIFFUSE")
                 .help("Diffusing method")
                 .takes_value(true)
-                .possible_values(&DiffuseMethod::VARIANTS),
+                .possible_values(DiffuseMethod::VARIANTS),
         )
         .arg(
             Arg::with_name("verbose")
                 .short("v")
                 .long("verbose")
                 .takes_value(false)
-                .help("Verbosity, prints remaining scanline"),
+                .help("Verbosity, prints remaining tiles"),
+        )
+        .arg(
+            Arg::with_name("scene")
+                .short("s")
+                .long("scene")
+                .value_name("PATH")
+                .help("Scene description file (TOML) - defaults to the built-in scene")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("builtin")
+                .long("builtin")
+                .value_name("SCENE")
+                .help("Scene built in code - defaults to random")
+                .takes_value(true)
+                .possible_values(BuiltinScene::VARIANTS)
+                .conflicts_with("scene"),
+        )
+        .arg(
+            Arg::with_name("background")
+                .short("b")
+                .long("background")
+                .value_name("BACKGROUND")
+                .help("Background - gradient, black or #RRGGBB, overrides the scene's")
+                .takes_value(true)
+                .validator(|background| Background::from_str(&background).map(|_| ())),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .value_name("PIXELS")
+                .help("Image width - defaults to 600")
+                .takes_value(true)
+                .validator(is_positive),
+        )
+        .arg(
+            Arg::with_name("height")
+                .long("height")
+                .value_name("PIXELS")
+                .help("Image height - defaults to width / aspect")
+                .takes_value(true)
+                .validator(is_positive)
+                .conflicts_with("aspect"),
+        )
+        .arg(
+            Arg::with_name("aspect")
+                .long("aspect")
+                .value_name("RATIO")
+                .help("Aspect ratio as a number or fraction, e.g. 16/9 - defaults to 3/2")
+                .takes_value(true)
+                .validator(|aspect| parse_aspect(&aspect).map(|_| ())),
+        )
+        .arg(
+            Arg::with_name("spp")
+                .long("spp")
+                .value_name("SAMPLES")
+                .help("Samples per pixel, the minimum with --noise-threshold - defaults to 10")
+                .takes_value(true)
+                .validator(is_positive),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .value_name("BOUNCES")
+                .help("Maximum ray bounce depth - defaults to 50")
+                .takes_value(true)
+                .validator(is_positive),
+        )
+        .arg(
+            Arg::with_name("tile-size")
+                .long("tile-size")
+                .value_name("PIXELS")
+                .help("Side of the square render tiles - defaults to 32")
+                .takes_value(true)
+                .validator(is_positive),
+        )
+        .arg(
+            Arg::with_name("tile-order")
+                .long("tile-order")
+                .value_name("ORDER")
+                .help("Order the tiles are rendered in - defaults to spiral")
+                .takes_value(true)
+                .possible_values(TileOrder::VARIANTS),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed of all random numbers, same seed same image - defaults to 0")
+                .takes_value(true)
+                .validator(|seed| u64::from_str(&seed).map(|_| ()).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::with_name("sampler")
+                .long("sampler")
+                .value_name("SAMPLER")
+                .help("Sample generator for pixel, lens and scattering - defaults to independent")
+                .takes_value(true)
+                .possible_values(SamplerKind::VARIANTS),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .value_name("FILTER")
+                .help("Pixel reconstruction filter - defaults to box")
+                .takes_value(true)
+                .possible_values(FilterKind::VARIANTS),
+        )
+        .arg(
+            Arg::with_name("filter-radius")
+                .long("filter-radius")
+                .value_name("PIXELS")
+                .help("Filter radius - defaults to 0.5 (box), 1 (tent), 1.5 (gaussian) or 2")
+                .takes_value(true)
+                .validator(is_positive_number),
+        )
+        .arg(
+            Arg::with_name("noise-threshold")
+                .long("noise-threshold")
+                .value_name("ERROR")
+                .help("Adaptive sampling, samples pixels until their relative error is below it")
+                .takes_value(true)
+                .validator(is_positive_number),
+        )
+        .arg(
+            Arg::with_name("max-spp")
+                .long("max-spp")
+                .value_name("SAMPLES")
+                .help("Samples per pixel cap of adaptive sampling - defaults to 8 x spp")
+                .takes_value(true)
+                .validator(is_positive)
+                .requires("noise-threshold"),
+        )
+        .arg(
+            Arg::with_name("heatmap")
+                .long("heatmap")
+                .value_name("FILE")
+                .help("Writes the samples taken per pixel as an image, e.g. heatmap.png")
+                .takes_value(true)
+                .validator(|heatmap| match Sink::from_filename(&heatmap) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("Unsupported extension in `{}`", heatmap)),
+                }),
         )
         .get_matches();
 
@@ -56,25 +288,112 @@ pub fn parse_arguments() -> Args {
         .unwrap_or_else(|| DiffuseMethod::Lambert.into());
     let diffuse_method = DiffuseMethod::from_str(diffuse_str).unwrap();
 
-    let output_str = matches
+    let output = matches
         .value_of("output")
-        .unwrap_or_else(|| Sink::Window.into());
-    let output = Sink::from_str(output_str).unwrap();
+        .map(|output| Sink::from_str(output).unwrap());
 
-    let filename = if let Some(filename) = matches.value_of("filename") {
-        Some(format!("{}_{}.ppm", filename, diffuse_str))
-    } else if output != Sink::File {
-        None
-    } else {
-        Some("image.ppm".into())
+    // An extension on the filename wins over the output format
+    let (output, filename) = match (output, matches.value_of("filename")) {
+        (Some(Sink::Window), _) | (None, None) => (Sink::Window, None),
+        (_, Some(filename)) if Path::new(filename).extension().is_some() => {
+            match Sink::from_filename(filename) {
+                Some(sink) => (sink, Some(filename.to_string())),
+                None => clap::Error::with_description(
+                    &format!(
+                        "Unsupported extension in `{}`, expected .ppm, .png, .pfm, .hdr or .exr",
+                        filename
+                    ),
+                    clap::ErrorKind::InvalidValue,
+                )
+                .exit(),
+            }
+        }
+        (output, filename) => {
+            let output = output.unwrap_or(Sink::File);
+            let extension = output.extension().unwrap();
+            let filename = match filename {
+                Some(filename) => format!("{}_{}.{}", filename, diffuse_str, extension),
+                None => format!("image.{}", extension),
+            };
+            (output, Some(filename))
+        }
     };
 
     let verbose = matches.is_present("verbose");
 
+    let defaults = RenderSettings::default();
+    let image_width = value_t!(matches, "width", usize).unwrap_or(defaults.image_width);
+    let image_height = value_t!(matches, "height", usize).unwrap_or_else(|_| {
+        let aspect = matches
+            .value_of("aspect")
+            .map_or(DEFAULT_ASPECT_RATIO, |aspect| parse_aspect(aspect).unwrap());
+        ((image_width as f64 / aspect) as usize).max(1)
+    });
+    let samples_per_pixel = value_t!(matches, "spp", usize).unwrap_or(defaults.samples_per_pixel);
+    let base = RenderSettings::new(
+        image_width,
+        image_height,
+        samples_per_pixel,
+        value_t!(matches, "max-depth", usize).unwrap_or(defaults.max_depth),
+    );
+    let settings = RenderSettings {
+        tile_size: value_t!(matches, "tile-size", usize).unwrap_or(defaults.tile_size),
+        tile_order: matches
+            .value_of("tile-order")
+            .map_or(defaults.tile_order, |order| {
+                TileOrder::from_str(order).unwrap()
+            }),
+        seed: value_t!(matches, "seed", u64).unwrap_or(defaults.seed),
+        sampler: matches
+            .value_of("sampler")
+            .map_or(defaults.sampler, |sampler| {
+                SamplerKind::from_str(sampler).unwrap()
+            }),
+        filter: {
+            let kind = matches
+                .value_of("filter")
+                .map_or(defaults.filter.kind, |kind| FilterKind::from_str(kind).unwrap());
+            let radius = value_t!(matches, "filter-radius", f64)
+                .unwrap_or_else(|_| kind.default_radius());
+            Filter::new(kind, radius)
+        },
+        noise_threshold: value_t!(matches, "noise-threshold", f64).ok(),
+        // Adaptive sampling never takes fewer than the samples per pixel
+        max_samples_per_pixel: value_t!(matches, "max-spp", usize)
+            .unwrap_or(base.max_samples_per_pixel)
+            .max(samples_per_pixel),
+        ..base
+    };
+
     Args {
         output,
         filename,
+        heatmap: matches.value_of("heatmap").map(String::from),
         diffuse_method,
         verbose,
+        settings,
+        scene: matches.value_of("scene").map(String::from),
+        builtin: matches
+            .value_of("builtin")
+            .map_or(BuiltinScene::Random, |builtin| {
+                BuiltinScene::from_str(builtin).unwrap()
+            }),
+        background: matches
+            .value_of("background")
+            .map(|background| Background::from_str(background).unwrap()),
+        tone_mapper: ToneMapper::new(
+            matches
+                .value_of("tonemap")
+                .map_or(ToneMapOperator::Clamp, |operator| {
+                    ToneMapOperator::from_str(operator).unwrap()
+                }),
+            value_t!(matches, "exposure", f64).unwrap_or(0.),
+            value_t!(matches, "white-point", f64).unwrap_or(4.),
+        ),
+        exr_precision: matches
+            .value_of("exr-precision")
+            .map_or(ExrPrecision::Half, |precision| {
+                ExrPrecision::from_str(precision).unwrap()
+            }),
     }
 }
@@ -0,0 +1,77 @@
+use crate::core::{ray::Ray, vec3::Point3};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub minimum: Point3,
+    pub maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point3, maximum: Point3) -> Aabb {
+        Aabb { minimum, maximum }
+    }
+
+    /// A box that contains nothing, the identity for `surrounding`
+    pub fn empty() -> Aabb {
+        Aabb::new(
+            Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        )
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.minimum.min(other.minimum), self.maximum.max(other.maximum))
+    }
+
+    pub fn enclose(&self, point: Point3) -> Aabb {
+        Aabb::new(self.minimum.min(point), self.maximum.max(point))
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        if d.x < 0. || d.y < 0. || d.z < 0. {
+            return 0.;
+        }
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Index of the longest axis (0 = x, 1 = y, 2 = z)
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.clip(ray, t_min, t_max).is_some()
+    }
+
+    /// The part of `(t_min, t_max)` the ray spends inside the box, if any
+    pub fn clip(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        // Slab method, the division by zero yields infinities which compare correctly
+        for a in 0..3 {
+            let inv_d = 1. / ray.direction[a];
+            let mut t0 = (self.minimum[a] - ray.origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - ray.origin[a]) * inv_d;
+            if inv_d < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
@@ -0,0 +1,54 @@
+use std::str::FromStr;
+
+use crate::core::{color::Color, ray::Ray};
+
+/// What a ray sees when it escapes the scene
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Background {
+    /// Blue-white sky gradient along the y axis
+    #[default]
+    Gradient,
+    Solid(Color),
+    Black,
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_direction = ray.direction.normalize();
+                let t = 0.5 * (unit_direction.y + 1.);
+                (1. - t) * Color::new(1., 1., 1.) + t * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Solid(color) => *color,
+            Background::Black => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+/// Parses `gradient`, `black` or a solid `#RRGGBB` color
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gradient" => Ok(Background::Gradient),
+            "black" => Ok(Background::Black),
+            hex if hex.len() == 7 && hex.starts_with('#') => {
+                let channel = |range: std::ops::Range<usize>| {
+                    u8::from_str_radix(&hex[range], 16)
+                        .map_err(|_| format!("Invalid hex color `{}`", hex))
+                };
+                Ok(Background::Solid(Color::new_rgb(
+                    channel(1..3)?,
+                    channel(3..5)?,
+                    channel(5..7)?,
+                )))
+            }
+            _ => Err(format!(
+                "Invalid background `{}`, expected `gradient`, `black` or `#RRGGBB`",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use crate::core::{
+    aabb::Aabb,
+    hit::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    ray::Ray,
+};
+
+/// Number of buckets the centroids are binned into when evaluating the SAH
+const SAH_BUCKETS: usize = 12;
+/// Relative cost of traversing a node compared to intersecting a primitive
+const TRAVERSAL_COST: f64 = 0.125;
+/// Leaves are never made bigger than this, even when the SAH would prefer it
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy over the objects of a `HittableList`,
+/// built top-down with a binned surface area heuristic (SAH).
+pub enum BvhNode<T: Hittable + ?Sized> {
+    Leaf {
+        objects: Vec<Arc<T>>,
+        bbox: Aabb,
+    },
+    Interior {
+        left: Box<BvhNode<T>>,
+        right: Box<BvhNode<T>>,
+        bbox: Aabb,
+    },
+}
+
+struct Primitive<T: ?Sized> {
+    object: Arc<T>,
+    bbox: Aabb,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    count: usize,
+    bbox: Aabb,
+}
+
+impl<T: Hittable + ?Sized> BvhNode<T> {
+    /// Builds the hierarchy, panics if an object has no bounding box
+    pub fn new(list: HittableList<T>) -> BvhNode<T> {
+        let primitives = list
+            .objects
+            .into_iter()
+            .map(|object| {
+                let bbox = object
+                    .bounding_box()
+                    .expect("No bounding box in BvhNode constructor");
+                Primitive { object, bbox }
+            })
+            .collect();
+        BvhNode::build(primitives)
+    }
+
+    fn build(mut primitives: Vec<Primitive<T>>) -> BvhNode<T> {
+        let bbox = primitives
+            .iter()
+            .fold(Aabb::empty(), |acc, p| acc.surrounding(&p.bbox));
+
+        if primitives.len() <= 1 {
+            return BvhNode::leaf(primitives, bbox);
+        }
+
+        let centroid_bounds = primitives
+            .iter()
+            .fold(Aabb::empty(), |acc, p| acc.enclose(p.bbox.centroid()));
+        let axis = centroid_bounds.longest_axis();
+        let c_min = centroid_bounds.minimum[axis];
+        let c_extent = centroid_bounds.maximum[axis] - c_min;
+
+        if c_extent <= 0. {
+            // All centroids coincide, the SAH can't separate them
+            if primitives.len() <= MAX_LEAF_SIZE {
+                return BvhNode::leaf(primitives, bbox);
+            }
+            let right = primitives.split_off(primitives.len() / 2);
+            return BvhNode::interior(primitives, right, bbox);
+        }
+
+        let bucket_of = |p: &Primitive<T>| {
+            let b = (SAH_BUCKETS as f64 * (p.bbox.centroid()[axis] - c_min) / c_extent) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut buckets = [Bucket {
+            count: 0,
+            bbox: Aabb::empty(),
+        }; SAH_BUCKETS];
+        for p in &primitives {
+            let bucket = &mut buckets[bucket_of(p)];
+            bucket.count += 1;
+            bucket.bbox = bucket.bbox.surrounding(&p.bbox);
+        }
+
+        // Cost of splitting after each bucket, relative to the parent's area
+        let parent_area = bbox.surface_area();
+        let (best_split, best_cost) = (0..SAH_BUCKETS - 1)
+            .map(|split| {
+                let (below, above) = buckets.split_at(split + 1);
+                let sum = |bs: &[Bucket]| {
+                    bs.iter().fold((0, Aabb::empty()), |(n, b), bucket| {
+                        (n + bucket.count, b.surrounding(&bucket.bbox))
+                    })
+                };
+                let (n0, b0) = sum(below);
+                let (n1, b1) = sum(above);
+                let cost = TRAVERSAL_COST
+                    + (n0 as f64 * b0.surface_area() + n1 as f64 * b1.surface_area())
+                        / parent_area;
+                (split, cost)
+            })
+            .fold((0, f64::INFINITY), |best, candidate| {
+                if candidate.1 < best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+
+        let leaf_cost = primitives.len() as f64;
+        if primitives.len() <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+            return BvhNode::leaf(primitives, bbox);
+        }
+
+        let (left, right): (Vec<_>, Vec<_>) = primitives
+            .into_iter()
+            .partition(|p| bucket_of(p) <= best_split);
+        BvhNode::interior(left, right, bbox)
+    }
+
+    fn leaf(primitives: Vec<Primitive<T>>, bbox: Aabb) -> BvhNode<T> {
+        BvhNode::Leaf {
+            objects: primitives.into_iter().map(|p| p.object).collect(),
+            bbox,
+        }
+    }
+
+    fn interior(left: Vec<Primitive<T>>, right: Vec<Primitive<T>>, bbox: Aabb) -> BvhNode<T> {
+        BvhNode::Interior {
+            left: Box::new(BvhNode::build(left)),
+            right: Box::new(BvhNode::build(right)),
+            bbox,
+        }
+    }
+
+    fn bbox(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } => bbox,
+            BvhNode::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for BvhNode<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox().hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => {
+                let mut hit_record: Option<HitRecord> = None;
+                let mut closest_so_far = t_max;
+                for object in objects {
+                    if let Some(rec) = object.hit(ray, t_min, closest_so_far) {
+                        closest_so_far = rec.t;
+                        hit_record = Some(rec);
+                    }
+                }
+                hit_record
+            }
+            BvhNode::Interior { left, right, .. } => {
+                let hit_left = left.hit(ray, t_min, t_max);
+                let closest_so_far = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+                right.hit(ray, t_min, closest_so_far).or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(*self.bbox())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::core::vec3::Point3;
+    use crate::testing::sphere;
+
+    fn random_point(rng: &mut StdRng, extent: f64) -> Point3 {
+        Point3::new(
+            rng.gen_range(-extent..extent),
+            rng.gen_range(-extent..extent),
+            rng.gen_range(-extent..extent),
+        )
+    }
+
+    fn random_spheres(rng: &mut StdRng, count: usize) -> HittableList<dyn Hittable> {
+        let mut list: HittableList<dyn Hittable> = HittableList::new();
+        for _ in 0..count {
+            let center = random_point(rng, 10.);
+            list.add(Arc::new(sphere(center, rng.gen_range(0.05..1.))));
+        }
+        list
+    }
+
+    /// Checks that every box lies within its parent's, returns the primitives
+    /// found below `node`
+    fn check_nesting(node: &BvhNode<dyn Hittable>) -> usize {
+        let inside = |inner: &Aabb, outer: &Aabb| {
+            (0..3).all(|axis| {
+                outer.minimum[axis] <= inner.minimum[axis]
+                    && inner.maximum[axis] <= outer.maximum[axis]
+            })
+        };
+        match node {
+            BvhNode::Leaf { objects, bbox } => {
+                for object in objects {
+                    assert!(inside(&object.bounding_box().unwrap(), bbox));
+                }
+                objects.len()
+            }
+            BvhNode::Interior { left, right, bbox } => {
+                assert!(inside(left.bbox(), bbox) && inside(right.bbox(), bbox));
+                check_nesting(left) + check_nesting(right)
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_same_closest_hit_as_the_list() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for count in [1, 2, 5, 100] {
+            let list = random_spheres(&mut rng, count);
+            let bvh = BvhNode::new(list.clone());
+            for _ in 0..1000 {
+                let origin = random_point(&mut rng, 15.);
+                let ray = Ray::new(origin, random_point(&mut rng, 10.) - origin, 0.);
+                let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+                assert_eq!(bvh.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn boxes_nest_and_hold_every_primitive() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let bvh = BvhNode::new(random_spheres(&mut rng, 200));
+        assert_eq!(check_nesting(&bvh), 200);
+
+        // Coinciding centroids can't be split by the SAH
+        let mut list: HittableList<dyn Hittable> = HittableList::new();
+        for radius in 1..=10 {
+            list.add(Arc::new(sphere(Point3::new(1., 2., 3.), radius as f64)));
+        }
+        assert_eq!(check_nesting(&BvhNode::new(list)), 10);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::{
     ray::Ray,
+    sampler::Sampler,
     vec3::{Point3, Vec3},
 };
 
@@ -11,8 +12,10 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f64,
+    /// Rays are spread uniformly over the times the shutter is open
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -49,18 +52,37 @@ impl Camera {
             lower_left_corner,
             u,
             v,
-            w,
             lens_radius,
+            shutter_open: 0.,
+            shutter_close: 0.,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    /// Keeps the shutter open from `open` to `close`, objects moving in between
+    /// get blurred. Cameras start with an instant shutter at time 0.
+    pub fn with_shutter(self, open: f64, close: f64) -> Camera {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rd = self.lens_radius * Vec3::sample_in_unit_disk(sampler.get_2d());
         let offset = self.u * rd.x + self.v * rd.y;
+        // Instant shutters don't draw a sample, keeping still scenes' sequences as they were
+        let time = if self.shutter_close > self.shutter_open {
+            let u = sampler.get_1d();
+            self.shutter_open + u * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        };
 
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
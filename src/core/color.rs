@@ -1,5 +1,5 @@
 use num::clamp;
-use rand::{random, thread_rng, Rng};
+use rand::Rng;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
@@ -21,15 +21,44 @@ impl Color {
             let b = u8::from_str_radix(&hex_str[5..7], 16).expect(HEX_ERR);
             Color::new_rgb(r, g, b)
         } else {
-            panic!(HEX_ERR)
+            panic!("{}", HEX_ERR)
         }
     }
     pub fn new_rgb(r: u8, g: u8, b: u8) -> Color {
         Color::new(r as f64 / 255., g as f64 / 255., b as f64 / 255.)
     }
+    /// Relative luminance of linear Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+    pub fn max_component(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
     pub fn sqrt(&self) -> Color {
         Color::new(self.r.sqrt(), self.g.sqrt(), self.b.sqrt())
     }
+    /// Decodes an sRGB encoded color to linear intensities
+    pub fn srgb_to_linear(&self) -> Color {
+        let decode = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(self.r), decode(self.g), decode(self.b))
+    }
+    /// Encodes linear intensities with the sRGB transfer function (OETF)
+    pub fn linear_to_srgb(&self) -> Color {
+        let encode = |c: f64| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1. / 2.4) - 0.055
+            }
+        };
+        Color::new(encode(self.r), encode(self.g), encode(self.b))
+    }
     pub fn as_u8_slice(&self) -> [u8; 3] {
         [
             (255. * clamp(self.r, 0., 1.)) as u8,
@@ -37,11 +66,10 @@ impl Color {
             (255. * clamp(self.b, 0., 1.)) as u8,
         ]
     }
-    pub fn random() -> Color {
-        Color::new(random::<f64>(), random::<f64>(), random::<f64>())
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Color {
+        Color::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
     }
-    pub fn random_limit(min: f64, max: f64) -> Color {
-        let mut rng = thread_rng();
+    pub fn random_limit<R: Rng + ?Sized>(min: f64, max: f64, rng: &mut R) -> Color {
         Color::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -50,6 +78,12 @@ impl Color {
     }
 }
 
+impl From<[f64; 3]> for Color {
+    fn from(c: [f64; 3]) -> Color {
+        Color::new(c[0], c[1], c[2])
+    }
+}
+
 impl std::fmt::Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{{ r: {}, g: {}, b: {} }}", self.r, self.g, self.b)
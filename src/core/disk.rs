@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::core::{
+    aabb::Aabb,
+    hit::{set_face_normal, HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    vec3::{Length, Point3, Vec3},
+};
+
+/// Minimum extent of a disk's bounding box, axis-aligned disks are flat
+const BBOX_PADDING: f64 = 1e-4;
+const PARALLEL_EPS: f64 = 1e-8;
+
+/// Flat circle facing along `normal`. The hit's u is the angle around the normal
+/// and v the distance from the center, both in [0, 1].
+#[derive(Debug, Clone)]
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+    /// In-plane axes, u is measured from the first towards the second
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Disk {
+            center,
+            normal,
+            radius,
+            material,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(ray.direction);
+        if denom.abs() < PARALLEL_EPS {
+            // Ray parallel to the plane
+            return None;
+        }
+        let t = self.normal.dot(self.center - ray.origin) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let offset = p - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+        let phi = offset.dot(self.bitangent).atan2(offset.dot(self.tangent));
+        let phi = if phi < 0. { phi + 2. * PI } else { phi };
+
+        let (front_face, normal) = set_face_normal(ray, self.normal);
+        Some(HitRecord {
+            p,
+            t,
+            u: phi / (2. * PI),
+            v: distance_squared.sqrt() / self.radius,
+            front_face,
+            normal,
+            material: self.material.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Extent of the circle along each axis, radius times the in-plane share of it
+        let extent = |n: f64| self.radius * (1. - n * n).max(0.).sqrt() + BBOX_PADDING / 2.;
+        let extent = Vec3::new(
+            extent(self.normal.x),
+            extent(self.normal.y),
+            extent(self.normal.z),
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    /// Uniform area density converted to solid angle at `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction, 0.), 0.001, f64::INFINITY) {
+            Some(rec) => {
+                let distance_squared = rec.t * rec.t * direction.length_squared();
+                let cosine = (direction.dot(self.normal) / direction.length()).abs();
+                distance_squared / (cosine * PI * self.radius * self.radius)
+            }
+            None => 0.,
+        }
+    }
+
+    fn random(&self, origin: &Point3, u: (f64, f64)) -> Vec3 {
+        let disk = Vec3::sample_in_unit_disk(u);
+        let point =
+            self.center + self.radius * (disk.x * self.tangent + disk.y * self.bitangent);
+        (point - *origin).normalize()
+    }
+}
@@ -0,0 +1,383 @@
+use std::{fmt, fs, path::Path};
+
+/// Voxels along each side of a block, grids are stored as blocks of 8³ voxels
+const BLOCK_SIZE: usize = 8;
+const BLOCK_VOXELS: usize = BLOCK_SIZE * BLOCK_SIZE * BLOCK_SIZE;
+
+const DENSE_MAGIC: &[u8; 4] = b"WVOL";
+const SPARSE_MAGIC: &[u8; 4] = b"WVSB";
+/// Most blocks a sparse grid can span, even empty ones take a slot in the index.
+/// 2²⁴ is a grid of 2048³ voxels.
+const MAX_SPARSE_BLOCKS: usize = 1 << 24;
+
+#[derive(Debug)]
+pub enum GridError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Truncated or malformed file
+    Format { path: String, message: String },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::Io { path, source } => {
+                write!(f, "Could not read volume {}: {}", path, source)
+            }
+            GridError::Format { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+/// Scalar density per voxel, e.g. a frame of a smoke simulation.
+///
+/// Stored sparsely in blocks of 8³ voxels, blocks that are empty take no memory.
+/// Samples are interpolated trilinearly between voxel centers.
+#[derive(Debug, Clone)]
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    /// Blocks along each axis, rounded up
+    blocks_per_axis: [usize; 3],
+    /// `None` for blocks where every voxel is zero, x varies fastest
+    blocks: Vec<Option<Box<[f32]>>>,
+    max_density: f64,
+}
+
+impl DensityGrid {
+    /// Grid of zeros
+    pub fn empty(resolution: [usize; 3]) -> DensityGrid {
+        let blocks_per_axis = resolution.map(|n| n.div_ceil(BLOCK_SIZE));
+        DensityGrid {
+            resolution,
+            blocks_per_axis,
+            blocks: vec![None; blocks_per_axis.iter().product()],
+            max_density: 0.,
+        }
+    }
+
+    /// Grid with the voxel at `(x, y, z)` set to `density(x, y, z)`, negative
+    /// values are clamped to zero
+    pub fn from_fn<F>(resolution: [usize; 3], density: F) -> DensityGrid
+    where
+        F: Fn(usize, usize, usize) -> f32,
+    {
+        let mut grid = DensityGrid::empty(resolution);
+        for z in 0..resolution[2] {
+            for y in 0..resolution[1] {
+                for x in 0..resolution[0] {
+                    grid.set(x, y, z, density(x, y, z));
+                }
+            }
+        }
+        grid
+    }
+
+    /// Reads a grid file, either
+    ///
+    /// - dense: `WVOL`, the resolution as three `u32`, then an `f32` per voxel
+    ///   with x varying fastest and z slowest
+    /// - sparse: `WVSB`, the resolution as three `u32`, the number of blocks as a
+    ///   `u32`, then per block its coordinates as three `u32` and 8³ `f32` voxels
+    ///   in the same order. Blocks that aren't listed are empty.
+    ///
+    /// Numbers are little-endian, densities must be finite and can't be negative.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<DensityGrid, GridError> {
+        let path_str = path.as_ref().display().to_string();
+        let bytes = fs::read(&path).map_err(|source| GridError::Io {
+            path: path_str.clone(),
+            source,
+        })?;
+        DensityGrid::parse(&bytes).map_err(|message| GridError::Format {
+            path: path_str,
+            message,
+        })
+    }
+
+    /// Parses the contents of a grid file, see `open`
+    pub fn parse(bytes: &[u8]) -> Result<DensityGrid, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let magic = reader.take(4)?;
+        let sparse = if magic == DENSE_MAGIC {
+            false
+        } else if magic == SPARSE_MAGIC {
+            true
+        } else {
+            return Err("Not a volume grid, the file doesn't start with WVOL or WVSB".into());
+        };
+        let resolution = [reader.u32()?, reader.u32()?, reader.u32()?];
+        if resolution.contains(&0) {
+            return Err(format!("Empty grid resolution {:?}", resolution));
+        }
+        // Checked against the size of the file before anything is allocated, so a
+        // bogus resolution can't exhaust memory
+        let too_large = || format!("Grid resolution {:?} is too large", resolution);
+
+        if !sparse {
+            let voxels = checked_product(&resolution).ok_or_else(too_large)?;
+            if voxels.checked_mul(4).is_none_or(|size| reader.remaining() < size) {
+                return Err(format!("Expected {} voxels, the file is too short", voxels));
+            }
+            let mut grid = DensityGrid::empty(resolution);
+            for z in 0..resolution[2] {
+                for y in 0..resolution[1] {
+                    for x in 0..resolution[0] {
+                        grid.set(x, y, z, reader.density()?);
+                    }
+                }
+            }
+            return Ok(grid);
+        }
+
+        let count = reader.u32()?;
+        // Each block takes its coordinates and voxels, more than the rest of the
+        // file holds can't be there
+        let block_bytes = 4 * (3 + BLOCK_VOXELS);
+        if count > reader.remaining() / block_bytes {
+            return Err(format!("Expected {} blocks, the file is too short", count));
+        }
+        let blocks = checked_product(&resolution.map(|n| n.div_ceil(BLOCK_SIZE)));
+        if blocks.is_none_or(|blocks| blocks > MAX_SPARSE_BLOCKS) {
+            return Err(too_large());
+        }
+        let mut grid = DensityGrid::empty(resolution);
+        for _ in 0..count {
+            let block = [reader.u32()?, reader.u32()?, reader.u32()?];
+            if (0..3).any(|axis| block[axis] >= grid.blocks_per_axis[axis]) {
+                return Err(format!("Block {:?} lies outside of the grid", block));
+            }
+            for z in 0..BLOCK_SIZE {
+                for y in 0..BLOCK_SIZE {
+                    for x in 0..BLOCK_SIZE {
+                        let density = reader.density()?;
+                        let (x, y, z) = (
+                            block[0] * BLOCK_SIZE + x,
+                            block[1] * BLOCK_SIZE + y,
+                            block[2] * BLOCK_SIZE + z,
+                        );
+                        // Blocks on the far sides pad the grid, their excess is dropped
+                        if x < resolution[0] && y < resolution[1] && z < resolution[2] {
+                            grid.set(x, y, z, density);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(grid)
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    /// Largest voxel, no interpolated sample is denser
+    pub fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    fn set(&mut self, x: usize, y: usize, z: usize, density: f32) {
+        let density = density.max(0.);
+        let (block, voxel) = self.locate(x, y, z);
+        match &mut self.blocks[block] {
+            Some(voxels) => voxels[voxel] = density,
+            None if density == 0. => return,
+            empty => {
+                let mut voxels = vec![0.; BLOCK_VOXELS].into_boxed_slice();
+                voxels[voxel] = density;
+                *empty = Some(voxels);
+            }
+        }
+        self.max_density = self.max_density.max(density as f64);
+    }
+
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        let (block, voxel) = self.locate(x, y, z);
+        self.blocks[block].as_ref().map_or(0., |voxels| voxels[voxel])
+    }
+
+    /// Indices of the block and of the voxel within it
+    fn locate(&self, x: usize, y: usize, z: usize) -> (usize, usize) {
+        let [bx, by, _] = self.blocks_per_axis;
+        let block = (z / BLOCK_SIZE * by + y / BLOCK_SIZE) * bx + x / BLOCK_SIZE;
+        let (x, y, z) = (x % BLOCK_SIZE, y % BLOCK_SIZE, z % BLOCK_SIZE);
+        (block, (z * BLOCK_SIZE + y) * BLOCK_SIZE + x)
+    }
+
+    /// Density at `p` in [0, 1]³ spanning the whole grid, the outermost voxels
+    /// extend to its faces
+    pub fn sample(&self, p: [f64; 3]) -> f64 {
+        let mut base = [0; 3];
+        let mut weights = [0.; 3];
+        for axis in 0..3 {
+            let n = self.resolution[axis];
+            // Voxel centers sit at half-integers
+            let x = (p[axis] * n as f64 - 0.5).clamp(0., (n - 1) as f64);
+            base[axis] = (x as usize).min(n.saturating_sub(2));
+            weights[axis] = x - base[axis] as f64;
+        }
+
+        let mut density = 0.;
+        for corner in 0..8 {
+            let mut weight = 1.;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let upper = corner & (1 << axis) != 0;
+                weight *= if upper { weights[axis] } else { 1. - weights[axis] };
+                index[axis] = (base[axis] + upper as usize).min(self.resolution[axis] - 1);
+            }
+            if weight > 0. {
+                density += weight * self.voxel(index[0], index[1], index[2]) as f64;
+            }
+        }
+        density
+    }
+}
+
+/// Product of `values`, `None` if it overflows
+fn checked_product(values: &[usize]) -> Option<usize> {
+    values.iter().try_fold(1usize, |product, &n| product.checked_mul(n))
+}
+
+/// Little-endian cursor over the bytes of a grid file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < count {
+            return Err(format!("Unexpected end of file at byte {}", self.offset));
+        }
+        let bytes = &self.bytes[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn density(&mut self) -> Result<f32, String> {
+        let offset = self.offset;
+        let bytes = self.take(4)?;
+        let density = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if !density.is_finite() || density < 0. {
+            return Err(format!("Invalid density {} at byte {}", density, offset));
+        }
+        Ok(density)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(magic: &[u8; 4], resolution: [u32; 3]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        for n in resolution {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn push_f32s(bytes: &mut Vec<u8>, values: impl IntoIterator<Item = f32>) {
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn parses_dense_grid() {
+        let mut bytes = header(DENSE_MAGIC, [2, 1, 3]);
+        push_f32s(&mut bytes, (0..6).map(|i| i as f32));
+        let grid = DensityGrid::parse(&bytes).unwrap();
+        assert_eq!(grid.resolution(), [2, 1, 3]);
+        assert_eq!(grid.voxel(1, 0, 0), 1.);
+        assert_eq!(grid.voxel(0, 0, 2), 4.);
+        assert_eq!(grid.max_density(), 5.);
+    }
+
+    #[test]
+    fn parses_sparse_grid() {
+        let mut bytes = header(SPARSE_MAGIC, [10, 8, 8]);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for coordinate in [1u32, 0, 0] {
+            bytes.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        push_f32s(&mut bytes, (0..BLOCK_VOXELS).map(|_| 2.));
+        let grid = DensityGrid::parse(&bytes).unwrap();
+        assert_eq!(grid.voxel(7, 0, 0), 0.);
+        assert_eq!(grid.voxel(9, 7, 7), 2.);
+        assert_eq!(grid.max_density(), 2.);
+    }
+
+    #[test]
+    fn uniform_grid_samples_its_density() {
+        let grid = DensityGrid::from_fn([3, 4, 5], |_, _, _| 0.5);
+        for p in [[0., 0., 0.], [0.3, 0.6, 0.9], [1., 1., 1.]] {
+            assert!((grid.sample(p) - 0.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_empty_resolution() {
+        assert!(DensityGrid::parse(b"VDB!").is_err());
+        assert!(DensityGrid::parse(&header(DENSE_MAGIC, [4, 0, 4])).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_dense_grid() {
+        let mut bytes = header(DENSE_MAGIC, [2, 2, 2]);
+        push_f32s(&mut bytes, [1.; 7]);
+        assert!(DensityGrid::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_resolution_without_allocating() {
+        let error = DensityGrid::parse(&header(DENSE_MAGIC, [100_000; 3])).unwrap_err();
+        assert!(error.contains("too short"), "{}", error);
+
+        let error = DensityGrid::parse(&header(DENSE_MAGIC, [u32::MAX; 3])).unwrap_err();
+        assert!(error.contains("too large"), "{}", error);
+
+        let mut bytes = header(SPARSE_MAGIC, [u32::MAX; 3]);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        assert!(DensityGrid::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_more_sparse_blocks_than_the_file_holds() {
+        let mut bytes = header(SPARSE_MAGIC, [8, 8, 8]);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(DensityGrid::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_sparse_block_outside_of_the_grid() {
+        let mut bytes = header(SPARSE_MAGIC, [8, 8, 8]);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for coordinate in [0u32, 1, 0] {
+            bytes.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        push_f32s(&mut bytes, (0..BLOCK_VOXELS).map(|_| 1.));
+        assert!(DensityGrid::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_densities() {
+        for density in [f32::INFINITY, f32::NAN, -1.] {
+            let mut bytes = header(DENSE_MAGIC, [1, 1, 1]);
+            push_f32s(&mut bytes, [density]);
+            assert!(DensityGrid::parse(&bytes).is_err(), "accepted {}", density);
+        }
+    }
+}
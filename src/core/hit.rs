@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::core::{
+    aabb::Aabb,
     material::Material,
     ray::Ray,
     vec3::{Point3, Vec3},
@@ -10,6 +11,9 @@ use crate::core::{
 pub struct HitRecord {
     pub p: Point3,
     pub t: f64,
+    /// Surface coordinates of the hit point
+    pub u: f64,
+    pub v: f64,
     pub material: Arc<dyn Material>,
     pub normal: Vec3,
     pub front_face: bool,
@@ -28,6 +32,26 @@ pub fn set_face_normal(ray: &Ray, outward_normal: Vec3) -> (bool, Vec3) {
     (front_face, normal)
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    /// Box enclosing the object, `None` for unbounded objects (e.g. infinite planes)
+    fn bounding_box(&self) -> Option<Aabb>;
+    /// Density over solid angle of `random` picking `direction` from `origin`,
+    /// zero for objects that can't be sampled as lights
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.
+    }
+    /// Direction from `origin` towards a point of the object, warped from a 2D sample
+    fn random(&self, _origin: &Point3, _u: (f64, f64)) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
+    /// Fraction of the light getting through the object between `t_min` and
+    /// `t_max` along the ray, an estimate for volumes. By default anything hit
+    /// blocks all of it.
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.hit(ray, t_min, t_max) {
+            Some(_) => 0.,
+            None => 1.,
+        }
+    }
 }
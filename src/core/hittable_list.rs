@@ -2,16 +2,18 @@ use std::sync::Arc;
 use std::vec::Vec;
 
 use crate::core::{
+    aabb::Aabb,
     hit::{HitRecord, Hittable},
     ray::Ray,
+    vec3::{Point3, Vec3},
 };
 
-#[derive(Debug, Clone)]
-pub struct HittableList<T: Hittable> {
+#[derive(Debug)]
+pub struct HittableList<T: Hittable + ?Sized> {
     pub objects: Vec<Arc<T>>,
 }
 
-impl <T: Hittable> HittableList<T> {
+impl <T: Hittable + ?Sized> HittableList<T> {
     pub fn new() -> HittableList<T> {
         HittableList {
             objects: Vec::new()
@@ -29,7 +31,22 @@ impl <T: Hittable> HittableList<T> {
 
 }
 
-impl <T: Hittable> Hittable for HittableList<T> {
+// Manual impl as cloning only bumps the reference counts, `T` needn't be `Clone`
+impl <T: Hittable + ?Sized> Clone for HittableList<T> {
+    fn clone(&self) -> Self {
+        HittableList {
+            objects: self.objects.clone(),
+        }
+    }
+}
+
+impl <T: Hittable + ?Sized> Default for HittableList<T> {
+    fn default() -> Self {
+        HittableList::new()
+    }
+}
+
+impl <T: Hittable + ?Sized> Hittable for HittableList<T> {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut hit_record: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
@@ -43,4 +60,48 @@ impl <T: Hittable> Hittable for HittableList<T> {
 
         hit_record
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        self.objects.iter().try_fold(Aabb::empty(), |acc, object| {
+            object.bounding_box().map(|bbox| acc.surrounding(&bbox))
+        })
+    }
+
+    /// Mixture of the objects' densities, each picked with equal probability
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    /// Light has to get through every object
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.;
+        for object in &self.objects {
+            transmittance *= object.transmittance(ray, t_min, t_max);
+            if transmittance == 0. {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    fn random(&self, origin: &Point3, u: (f64, f64)) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        // The first dimension picks the object and is stretched back to [0, 1)
+        let scaled = u.0 * self.objects.len() as f64;
+        let index = (scaled as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin, (scaled - index as f64, u.1))
+    }
 }
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use crate::core::{
+    aabb::Aabb,
+    hit::{HitRecord, Hittable},
+    ray::Ray,
+    transform::{AnimatedTransform, Transform},
+    vec3::{Length, Point3, Vec3},
+};
+
+/// A shared object placed in the world by a transform. The object is never copied,
+/// so a mesh can be instanced any number of times for the cost of its transforms.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    to_world: Transform,
+    to_object: Transform,
+    motion: Option<Motion>,
+    bbox: Option<Aabb>,
+    /// Determinant of the linear part, how much the transform scales volumes
+    determinant: f64,
+}
+
+/// Motion from the instance's own transform at `time0` to another at `time1`
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    transform: AnimatedTransform,
+    time0: f64,
+    time1: f64,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        let bbox = object.bounding_box().map(|bbox| transform.bbox(&bbox));
+        let m = &transform.matrix().m;
+        let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        Instance {
+            object,
+            to_world: transform,
+            to_object: transform.inverse(),
+            motion: None,
+            bbox,
+            determinant,
+        }
+    }
+
+    /// Instance moving from `start` at `time0` to `end` at `time1` and resting at
+    /// either end outside of it, see `AnimatedTransform` for the path in between.
+    /// Light sampling only sees the object at `start`, so moving emitters
+    /// shouldn't be added to the lights.
+    pub fn moving(
+        object: Arc<dyn Hittable>,
+        start: Transform,
+        end: Transform,
+        time0: f64,
+        time1: f64,
+    ) -> Instance {
+        let instance = Instance::new(object, start);
+        let transform = AnimatedTransform::new(start, end);
+        if transform.is_static() || time1 == time0 {
+            return instance;
+        }
+        let bbox = instance.object.bounding_box().map(|bbox| transform.bbox(&bbox));
+        Instance {
+            motion: Some(Motion {
+                transform,
+                time0,
+                time1,
+            }),
+            bbox,
+            ..instance
+        }
+    }
+
+    /// Object to world transform at `time`, `None` if it's singular then
+    fn to_world_at(&self, time: f64) -> Option<Transform> {
+        match self.motion {
+            Some(Motion {
+                transform,
+                time0,
+                time1,
+            }) => transform.at((time - time0) / (time1 - time0)),
+            None => Some(self.to_world),
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let to_world = self.to_world_at(ray.time)?;
+        // The object space direction isn't normalized, so `t` is the same in both spaces
+        let mut rec = self.object.hit(&to_world.inverse().ray(ray), t_min, t_max)?;
+        rec.p = to_world.point(rec.p);
+        // Facing is kept, the dot product of normal and direction doesn't change sign
+        rec.normal = to_world.normal(rec.normal).normalize();
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.to_world_at(ray.time) {
+            Some(to_world) => self.object.transmittance(&to_world.inverse().ray(ray), t_min, t_max),
+            None => 1.,
+        }
+    }
+
+    /// The object's density at the object space direction, divided by how much
+    /// the linear part stretches solid angle around it
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let local = self.to_object.vector(direction.normalize());
+        let pdf = self
+            .object
+            .pdf_value(&self.to_object.point(*origin), &local.normalize());
+        // dω' / dω = |det A| / |A ω|³ for the unit object space ω, and |A ω| = 1 / |local|
+        pdf / (self.determinant.abs() * local.length().powi(3))
+    }
+
+    fn random(&self, origin: &Point3, u: (f64, f64)) -> Vec3 {
+        let local = self.object.random(&self.to_object.point(*origin), u);
+        self.to_world.vector(local).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{placed, unit_sphere};
+
+    #[test]
+    fn hits_the_transformed_object() {
+        let instance = Instance::new(unit_sphere(), placed());
+        // Stretched along x then turned upright, the ellipsoid reaches up to y = 2
+        let ray = Ray::new(Point3::new(0., 5., -5.), Vec3::new(0., -1., 0.), 0.);
+        let rec = instance.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.).abs() < 1e-9);
+        assert!((rec.p - Point3::new(0., 2., -5.)).length() < 1e-9);
+        assert!((rec.normal - Vec3::new(0., 1., 0.)).length() < 1e-9);
+
+        let miss = Ray::new(Point3::new(1.5, 5., -5.), Vec3::new(0., -1., 0.), 0.);
+        assert!(instance.hit(&miss, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn bounding_box_covers_the_transformed_object() {
+        let bbox = Instance::new(unit_sphere(), placed()).bounding_box().unwrap();
+        assert!((bbox.minimum - Point3::new(-1., -2., -6.)).length() < 1e-9);
+        assert!((bbox.maximum - Point3::new(1., 2., -4.)).length() < 1e-9);
+    }
+
+    #[test]
+    fn moving_instances_rest_outside_of_their_interval() {
+        let end = Transform::translate(Vec3::new(4., 0., 0.));
+        let instance = Instance::moving(unit_sphere(), Transform::identity(), end, 0., 1.);
+        let bbox = instance.bounding_box().unwrap();
+        assert_eq!((bbox.minimum.x, bbox.maximum.x), (-1., 5.));
+
+        let at = |x: f64, time: f64| {
+            let ray = Ray::new(Point3::new(x, 0., 5.), Vec3::new(0., 0., -1.), time);
+            instance.hit(&ray, 0.001, f64::INFINITY).is_some()
+        };
+        assert!(at(0., -1.) && !at(4., -1.));
+        assert!(at(2., 0.5) && !at(0., 0.5));
+        assert!(at(4., 2.) && !at(0., 2.));
+    }
+
+    #[test]
+    fn moving_instance_without_motion_is_static() {
+        let instance = Instance::moving(unit_sphere(), placed(), placed(), 0., 1.);
+        assert!(instance.motion.is_none());
+    }
+}
@@ -1,8 +1,17 @@
+use std::f64::consts::PI;
+
 use enum_iterator::IntoEnumIterator;
-use strum_macros::{EnumString, EnumVariantNames, IntoStaticStr, ToString};
-use rand::random;
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+use std::sync::Arc;
 
-use crate::core::{color::Color, hit::HitRecord, ray::Ray, vec3::Vec3};
+use crate::core::{
+    color::Color,
+    hit::HitRecord,
+    ray::Ray,
+    sampler::Sampler,
+    texture::Texture,
+    vec3::{Length, Point3, Vec3},
+};
 
 #[derive(
     Debug,
@@ -13,33 +22,117 @@ use crate::core::{color::Color, hit::HitRecord, ray::Ray, vec3::Vec3};
     EnumVariantNames,
     IntoStaticStr,
     IntoEnumIterator,
-    ToString,
+    Display,
 )]
 #[strum(serialize_all = "kebab_case")]
 pub enum DiffuseMethod {
+    /// The normal plus a point in the unit ball, density grows with the cosine cubed
     Simple,
+    /// Cosine-weighted, the density matches the BSDF
     Lambert,
+    /// Uniform over the hemisphere
     Hemisphere,
 }
 
+/// How a hit scatters light, returned by `Material::scatter`.
+///
+/// Directions point away from the surface, towards where the light comes from.
+pub enum Scatter {
+    /// Delta distribution (mirrors, glass), light only arrives along `ray` so it
+    /// can't be evaluated in other directions
+    Specular { attenuation: Color, ray: Ray },
+    /// Continuous distribution leaving `origin` at `time` that can be sampled and
+    /// evaluated
+    Bsdf {
+        origin: Point3,
+        time: f64,
+        bsdf: Box<dyn Bsdf>,
+    },
+}
+
+/// A scattered ray with its Monte Carlo weight
 #[derive(Debug, Clone, Copy)]
-pub struct Scatter {
-    pub attenuation: Color,
+pub struct ScatterSample {
     pub ray: Ray,
+    /// `eval` over `pdf` of the direction, the attenuation of specular scattering
+    pub weight: Color,
+    /// Density over solid angle the direction was sampled with, `None` for specular
+    /// scattering where it's a delta
+    pub pdf: Option<f64>,
+}
+
+impl Scatter {
+    pub fn is_specular(&self) -> bool {
+        matches!(self, Scatter::Specular { .. })
+    }
+
+    /// Density over solid angle of `sample` picking the unit `direction`
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        match self {
+            Scatter::Specular { .. } => 0.,
+            Scatter::Bsdf { bsdf, .. } => bsdf.pdf(direction),
+        }
+    }
+
+    /// BSDF times the cosine to the normal for light arriving from the unit `direction`
+    pub fn eval(&self, direction: &Vec3) -> Color {
+        match self {
+            Scatter::Specular { .. } => Color::new(0., 0., 0.),
+            Scatter::Bsdf { bsdf, .. } => bsdf.eval(direction),
+        }
+    }
+
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Option<ScatterSample> {
+        match self {
+            Scatter::Specular { attenuation, ray } => Some(ScatterSample {
+                ray: *ray,
+                weight: *attenuation,
+                pdf: None,
+            }),
+            Scatter::Bsdf { origin, time, bsdf } => {
+                let direction = bsdf.sample(sampler)?;
+                let pdf = bsdf.pdf(&direction);
+                if pdf <= 0. {
+                    return None;
+                }
+                Some(ScatterSample {
+                    ray: Ray::new(*origin, direction, *time),
+                    weight: bsdf.eval(&direction) / pdf,
+                    pdf: Some(pdf),
+                })
+            }
+        }
+    }
+}
+
+/// Scattering distribution at a hit, set up by the material in the hit's frame
+pub trait Bsdf {
+    /// BSDF times the cosine to the normal for light arriving from the unit `direction`
+    fn eval(&self, direction: &Vec3) -> Color;
+    /// Density over solid angle of `sample` picking the unit `direction`
+    fn pdf(&self, direction: &Vec3) -> f64;
+    /// A unit direction, `None` if the sample doesn't give one
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3>;
 }
 
 pub trait Material: std::fmt::Debug + Send + Sync {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Scatter>;
+    /// How light scatters at the hit, `None` if it's absorbed
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler)
+        -> Option<Scatter>;
+    /// Light given off by the surface, black for anything that isn't a light source
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::new(0., 0., 0.)
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: Arc<dyn Texture>,
     pub diffuse_method: DiffuseMethod,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Color, diffuse_method: DiffuseMethod) -> Lambertian {
+    pub fn new(albedo: Arc<dyn Texture>, diffuse_method: DiffuseMethod) -> Lambertian {
         Lambertian {
             albedo,
             diffuse_method,
@@ -48,52 +141,332 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, rec: &HitRecord) -> Option<Scatter> {
-        let scatter_direction = {
-            let candidate = match self.diffuse_method {
-                DiffuseMethod::Hemisphere => Vec3::random_in_hemisphere(&rec.normal),
-                DiffuseMethod::Simple => rec.normal + Vec3::random_in_unit_sphere(),
-                DiffuseMethod::Lambert => rec.normal + Vec3::random_unit_vector(),
-            };
-            // Catch degenerate scatter direction
-            if candidate.near_zero() {
-                rec.normal
-            } else {
-                candidate
-            }
-        };
-        Some(Scatter {
-            ray: Ray::new(rec.p, scatter_direction),
-            attenuation: self.albedo,
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, _: &mut dyn Sampler) -> Option<Scatter> {
+        Some(Scatter::Bsdf {
+            origin: rec.p,
+            time: ray_in.time,
+            bsdf: Box::new(LambertianBsdf {
+                albedo: self.albedo.value(rec.u, rec.v, &rec.p),
+                normal: rec.normal,
+                diffuse_method: self.diffuse_method,
+            }),
         })
     }
 }
 
+/// Ideal diffuse reflection, the diffuse method only picks how directions are
+/// sampled so every method converges to the same image
 #[derive(Debug, Clone, Copy)]
-pub struct Metal {
+pub struct LambertianBsdf {
     pub albedo: Color,
+    pub normal: Vec3,
+    pub diffuse_method: DiffuseMethod,
+}
+
+impl Bsdf for LambertianBsdf {
+    fn eval(&self, direction: &Vec3) -> Color {
+        self.albedo * (self.normal.dot(*direction).max(0.) / PI)
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let cosine = self.normal.dot(*direction);
+        if cosine <= 0. {
+            return 0.;
+        }
+        match self.diffuse_method {
+            // A point in the unit ball touching the surface, seen from the touching point
+            DiffuseMethod::Simple => 2. * cosine.powi(3) / PI,
+            DiffuseMethod::Lambert => cosine / PI,
+            DiffuseMethod::Hemisphere => 1. / (2. * PI),
+        }
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let u = sampler.get_2d();
+        let direction = match self.diffuse_method {
+            DiffuseMethod::Simple => {
+                let candidate = self.normal + Vec3::sample_in_unit_sphere(u, sampler.get_1d());
+                // Catch degenerate scatter direction
+                if candidate.near_zero() {
+                    self.normal
+                } else {
+                    candidate.normalize()
+                }
+            }
+            DiffuseMethod::Lambert => self.normal.from_local(Vec3::sample_cosine_hemisphere(u)),
+            DiffuseMethod::Hemisphere => Vec3::sample_in_hemisphere(&self.normal, u),
+        };
+        Some(direction)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Metal {
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f64) -> Metal {
+    pub fn new(albedo: Arc<dyn Texture>, fuzz: f64) -> Metal {
         Metal { albedo, fuzz }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let reflected = ray_in.direction.normalize().reflect(&rec.normal);
-        let ray = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
-        let attenuation = self.albedo;
+        let fuzz = Vec3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d());
+        let ray = Ray::new(rec.p, reflected + self.fuzz * fuzz, ray_in.time);
+        let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
         if ray.direction.dot(rec.normal) > 0. {
-            Some(Scatter { ray, attenuation })
+            Some(Scatter::Specular { ray, attenuation })
         } else {
             None
         }
     }
 }
 
+/// Metals with measured complex indices of refraction, per RGB channel
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum Conductor {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl Conductor {
+    /// Real part (eta) and extinction coefficient (k) of the index of refraction
+    pub fn ior(self) -> (Color, Color) {
+        match self {
+            Conductor::Gold => (Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603)),
+            Conductor::Copper => (Color::new(0.2, 0.924, 1.102), Color::new(3.912, 2.452, 2.142)),
+            Conductor::Aluminium => {
+                (Color::new(1.657, 0.88, 0.521), Color::new(9.224, 6.27, 4.837))
+            }
+            Conductor::Silver => (Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147)),
+        }
+    }
+}
+
+/// Below this roughness the microfacet distribution is too sharp to evaluate,
+/// conductors reflect as perfect mirrors instead
+const SMOOTH_ROUGHNESS: f64 = 1e-3;
+
+/// Physically based metal: GGX (Trowbridge-Reitz) microfacets with Smith's
+/// height-correlated masking-shadowing and the exact Fresnel reflectance of a
+/// complex index of refraction. Light scattering between microfacets more than
+/// once is lost, so very rough metals come out a little dark.
+#[derive(Debug, Clone, Copy)]
+pub struct RoughConductor {
+    pub eta: Color,
+    pub k: Color,
+    /// GGX roughness (α) along the tangent and bitangent, 0 is a mirror and
+    /// around 0.5 looks brushed
+    pub roughness: (f64, f64),
+    /// World space direction the first roughness is measured along, projected
+    /// onto the surface at every hit
+    pub tangent: Vec3,
+}
+
+impl RoughConductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> RoughConductor {
+        // The tangent doesn't matter when both roughnesses are the same
+        RoughConductor::anisotropic(eta, k, (roughness, roughness), Vec3::new(1., 0., 0.))
+    }
+
+    /// Highlights stretched along the rougher direction, `roughness.0` applies
+    /// along `tangent`. Hits have no surface derivatives, so the tangent is a fixed
+    /// world space direction, e.g. the axis a cylinder was brushed along. Where it's
+    /// parallel to the normal an arbitrary one is used.
+    pub fn anisotropic(
+        eta: Color,
+        k: Color,
+        roughness: (f64, f64),
+        tangent: Vec3,
+    ) -> RoughConductor {
+        RoughConductor {
+            eta,
+            k,
+            roughness,
+            tangent,
+        }
+    }
+
+    /// Tangent and bitangent at a hit with the `normal`
+    fn frame(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let tangent = self.tangent - self.tangent.dot(*normal) * *normal;
+        if tangent.length_squared() < 1e-12 {
+            return normal.orthonormal_basis();
+        }
+        let tangent = tangent.normalize();
+        (tangent, normal.cross(&tangent))
+    }
+
+    pub fn preset(conductor: Conductor, roughness: f64) -> RoughConductor {
+        let (eta, k) = conductor.ior();
+        RoughConductor::new(eta, k, roughness)
+    }
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, _: &mut dyn Sampler) -> Option<Scatter> {
+        let incoming = ray_in.direction.normalize();
+        let (alpha_x, alpha_y) = self.roughness;
+        if alpha_x.max(alpha_y) < SMOOTH_ROUGHNESS {
+            let cosine = (-incoming).dot(rec.normal).clamp(0., 1.);
+            return Some(Scatter::Specular {
+                attenuation: fresnel_conductor(cosine, self.eta, self.k),
+                ray: Ray::new(rec.p, incoming.reflect(&rec.normal), ray_in.time),
+            });
+        }
+
+        let (tangent, bitangent) = self.frame(&rec.normal);
+        let to_local = |v: Vec3| Vec3::new(v.dot(tangent), v.dot(bitangent), v.dot(rec.normal));
+        Some(Scatter::Bsdf {
+            origin: rec.p,
+            time: ray_in.time,
+            bsdf: Box::new(MicrofacetBsdf {
+                eta: self.eta,
+                k: self.k,
+                alpha_x: alpha_x.max(SMOOTH_ROUGHNESS),
+                alpha_y: alpha_y.max(SMOOTH_ROUGHNESS),
+                normal: rec.normal,
+                tangent,
+                bitangent,
+                outgoing: to_local(-incoming),
+            }),
+        })
+    }
+}
+
+/// GGX microfacet reflection, set up in the shading frame where the normal is +z
+#[derive(Debug, Clone, Copy)]
+pub struct MicrofacetBsdf {
+    pub eta: Color,
+    pub k: Color,
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+    pub normal: Vec3,
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    /// Unit direction towards the previous path vertex, in the shading frame
+    pub outgoing: Vec3,
+}
+
+impl MicrofacetBsdf {
+    fn local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.tangent), v.dot(self.bitangent), v.dot(self.normal))
+    }
+
+    /// Density of microfacet normals `h` per projected area
+    fn distribution(&self, h: &Vec3) -> f64 {
+        let (x, y) = (h.x / self.alpha_x, h.y / self.alpha_y);
+        let e = x * x + y * y + h.z * h.z;
+        1. / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's Λ, the masked share of microfacets seen from `w` relative to the
+    /// visible ones
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let (x, y) = (self.alpha_x * w.x, self.alpha_y * w.y);
+        ((1. + (x * x + y * y) / (w.z * w.z)).sqrt() - 1.) / 2.
+    }
+
+    /// Half vector of the local `incoming` direction, `None` below the surface
+    fn half_vector(&self, incoming: &Vec3) -> Option<Vec3> {
+        if incoming.z <= 0. || self.outgoing.z <= 0. {
+            return None;
+        }
+        let h = *incoming + self.outgoing;
+        if h.near_zero() {
+            return None;
+        }
+        Some(h.normalize())
+    }
+}
+
+impl Bsdf for MicrofacetBsdf {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let incoming = self.local(direction);
+        let h = match self.half_vector(&incoming) {
+            Some(h) => h,
+            None => return Color::new(0., 0., 0.),
+        };
+        let fresnel = fresnel_conductor(self.outgoing.dot(h).clamp(0., 1.), self.eta, self.k);
+        let shadowing = 1. / (1. + self.lambda(&self.outgoing) + self.lambda(&incoming));
+        // D F G / (4 cos_o cos_i), times cos_i
+        fresnel * (self.distribution(&h) * shadowing / (4. * self.outgoing.z))
+    }
+
+    /// Visible normals reflected, `D_v(h) / (4 o·h)` with `D_v(h) = G1(o) o·h D(h) / o_z`
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let incoming = self.local(direction);
+        match self.half_vector(&incoming) {
+            Some(h) => {
+                let masking = 1. / (1. + self.lambda(&self.outgoing));
+                masking * self.distribution(&h) / (4. * self.outgoing.z)
+            }
+            None => 0.,
+        }
+    }
+
+    /// Heitz's sampling of the normals visible from the outgoing direction
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let o = self.outgoing;
+        if o.z <= 0. {
+            return None;
+        }
+        // Stretched to the hemisphere configuration of a roughness of one
+        let v = Vec3::new(self.alpha_x * o.x, self.alpha_y * o.y, o.z).normalize();
+        let length_squared = v.x * v.x + v.y * v.y;
+        let t1 = if length_squared > 0. {
+            Vec3::new(-v.y, v.x, 0.) / length_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = v.cross(&t1);
+
+        // Point on the projected disk, the half hidden behind the hemisphere squeezed
+        let (u1, u2) = sampler.get_2d();
+        let r = u1.sqrt();
+        let phi = 2. * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + v.z);
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * v;
+
+        // Unstretched back to the microfacet normal
+        let h = Vec3::new(self.alpha_x * n.x, self.alpha_y * n.y, n.z.max(0.)).normalize();
+        let incoming = 2. * o.dot(h) * h - o;
+        if incoming.z <= 0. {
+            return None;
+        }
+        Some(incoming.x * self.tangent + incoming.y * self.bitangent + incoming.z * self.normal)
+    }
+}
+
+/// Unpolarized reflectance of a conductor with the index of refraction `eta + ik`
+/// per channel, at the cosine of the angle of incidence
+fn fresnel_conductor(cosine: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cosine * cosine;
+        let sin2 = 1. - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2. * cosine * a;
+        let s = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let p = s * (t3 - t4) / (t3 + t4);
+        0.5 * (s + p)
+    };
+    Color::new(channel(eta.r, k.r), channel(eta.g, k.g), channel(eta.b, k.b))
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Dielectric {
     /// Index of refraction:
@@ -115,7 +488,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let attenuation = Color::new_hex(b"#FFFFFF");
         let refraction_ratio = if rec.front_face {
             1. / self.ir
@@ -130,16 +503,333 @@ impl Material for Dielectric {
         let cannot_refract = refraction_ratio * sin_theta > 1.;
 
         let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > random::<f64>()
+            || Dielectric::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
         {
             unit_direction.reflect(&rec.normal)
         } else {
             unit_direction.refract(&rec.normal, refraction_ratio)
         };
 
-        Some(Scatter {
+        Some(Scatter::Specular {
             attenuation,
-            ray: Ray::new(rec.p, direction),
+            ray: Ray::new(rec.p, direction, ray_in.time),
+        })
+    }
+}
+
+/// Emits light uniformly from both sides of the surface and doesn't scatter
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+    pub emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Arc<dyn Texture>) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.emit.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/// Scatters light equally in every direction, the phase function of a medium's
+/// particles rather than a surface
+#[derive(Debug, Clone)]
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, _: &mut dyn Sampler) -> Option<Scatter> {
+        Some(Scatter::Bsdf {
+            origin: rec.p,
+            time: ray_in.time,
+            bsdf: Box::new(IsotropicPhase {
+                albedo: self.albedo.value(rec.u, rec.v, &rec.p),
+            }),
+        })
+    }
+}
+
+/// Uniform phase function, there's no surface so no cosine term either
+#[derive(Debug, Clone, Copy)]
+pub struct IsotropicPhase {
+    pub albedo: Color,
+}
+
+impl Bsdf for IsotropicPhase {
+    fn eval(&self, _: &Vec3) -> Color {
+        self.albedo / (4. * PI)
+    }
+
+    fn pdf(&self, _: &Vec3) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(Vec3::sample_unit_vector(sampler.get_2d()))
+    }
+}
+
+/// Henyey-Greenstein phase function of a medium's particles, `anisotropy` (g) in
+/// (-1, 1) goes from scattering back towards where the light came from, through
+/// isotropic at 0, to scattering forward
+#[derive(Debug, Clone)]
+pub struct HenyeyGreenstein {
+    pub albedo: Arc<dyn Texture>,
+    pub anisotropy: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Arc<dyn Texture>, anisotropy: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein { albedo, anisotropy }
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, ray_in: &Ray, rec: &HitRecord, _: &mut dyn Sampler) -> Option<Scatter> {
+        Some(Scatter::Bsdf {
+            origin: rec.p,
+            time: ray_in.time,
+            bsdf: Box::new(HenyeyGreensteinPhase {
+                albedo: self.albedo.value(rec.u, rec.v, &rec.p),
+                forward: ray_in.direction.normalize(),
+                g: self.anisotropy,
+            }),
         })
     }
 }
+
+/// Below this anisotropy the phase function is sampled as isotropic, the
+/// inverted CDF divides by g
+const ISOTROPIC_G: f64 = 1e-3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreensteinPhase {
+    pub albedo: Color,
+    /// Unit direction of the incoming ray, away from the previous path vertex.
+    /// Light travels the other way, so forward scattering (g > 0) continues the
+    /// path close to it.
+    pub forward: Vec3,
+    pub g: f64,
+}
+
+impl HenyeyGreensteinPhase {
+    /// Density of the cosine between `forward` and the direction the path carries
+    /// on in, towards where the light arrives from
+    fn density(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denom = 1. + g * g - 2. * g * cosine;
+        (1. - g * g) / (4. * PI * denom * denom.max(0.).sqrt())
+    }
+}
+
+impl Bsdf for HenyeyGreensteinPhase {
+    fn eval(&self, direction: &Vec3) -> Color {
+        self.albedo * self.density(self.forward.dot(*direction))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        self.density(self.forward.dot(*direction))
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (u, v) = sampler.get_2d();
+        let g = self.g;
+        let cos_theta = if g.abs() < ISOTROPIC_G {
+            1. - 2. * u
+        } else {
+            let s = (1. - g * g) / (1. - g + 2. * g * u);
+            ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * v;
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Some(self.forward.from_local(local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{hit::Hittable, sampler::independent::IndependentSampler, vec3::Length};
+    use crate::testing::sphere;
+
+    const COSINE_BINS: usize = 10;
+    const AZIMUTH_BINS: usize = 8;
+
+    /// Bin of a unit direction above the xy plane, by its cosine to +z and its azimuth
+    fn bin(direction: &Vec3) -> usize {
+        let band = ((direction.z * COSINE_BINS as f64) as usize).min(COSINE_BINS - 1);
+        let azimuth = (direction.y.atan2(direction.x) + PI) / (2. * PI);
+        let sector = ((azimuth * AZIMUTH_BINS as f64) as usize).min(AZIMUTH_BINS - 1);
+        band * AZIMUTH_BINS + sector
+    }
+
+    /// Checks that the fraction of `sample` directions in every bin is the integral
+    /// of `pdf` over it, for a BSDF with the normal along +z. Samples that give no
+    /// direction count towards no bin, neither does `pdf` below the surface.
+    fn assert_samples_follow_pdf(bsdf: &dyn Bsdf, count: usize) {
+        let mut sampler = IndependentSampler::new(5);
+        let mut sampled = [0.; COSINE_BINS * AZIMUTH_BINS];
+        for sample in 0..count {
+            sampler.start_pixel_sample(0, 0, sample);
+            if let Some(direction) = bsdf.sample(&mut sampler) {
+                assert!((direction.length() - 1.).abs() < 1e-9);
+                assert!(direction.z >= 0., "{} is below the surface", direction);
+                sampled[bin(&direction)] += 1. / count as f64;
+            }
+        }
+
+        let steps = 32;
+        let (band_width, sector_width) = (1. / COSINE_BINS as f64, 2. * PI / AZIMUTH_BINS as f64);
+        for (index, fraction) in sampled.iter().enumerate() {
+            let (band, sector) = (index / AZIMUTH_BINS, index % AZIMUTH_BINS);
+            let mut expected = 0.;
+            for i in 0..steps {
+                for j in 0..steps {
+                    let cosine = (band as f64 + (i as f64 + 0.5) / steps as f64) * band_width;
+                    let phi = (sector as f64 + (j as f64 + 0.5) / steps as f64) * sector_width;
+                    let phi = phi - PI;
+                    let sine = (1. - cosine * cosine).sqrt();
+                    let direction = Vec3::new(sine * phi.cos(), sine * phi.sin(), cosine);
+                    // Solid angle is the product of cosine and azimuth
+                    expected += bsdf.pdf(&direction) * band_width * sector_width
+                        / (steps * steps) as f64;
+                }
+            }
+            assert!(
+                (fraction - expected).abs() < 0.003,
+                "band {} sector {}: sampled {} but the pdf gives {}",
+                band,
+                sector,
+                fraction,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn diffuse_samples_follow_their_pdf() {
+        let normal = Vec3::new(0., 0., 1.);
+        for diffuse_method in DiffuseMethod::into_enum_iter() {
+            let bsdf = LambertianBsdf {
+                albedo: Color::new(0.5, 0.5, 0.5),
+                normal,
+                diffuse_method,
+            };
+            assert_samples_follow_pdf(&bsdf, 200_000);
+            assert_eq!(bsdf.pdf(&-normal), 0.);
+        }
+    }
+
+    /// GGX reflection in the frame of the world axes
+    fn microfacet(alpha_x: f64, alpha_y: f64, outgoing: Vec3) -> MicrofacetBsdf {
+        let (eta, k) = Conductor::Gold.ior();
+        MicrofacetBsdf {
+            eta,
+            k,
+            alpha_x,
+            alpha_y,
+            normal: Vec3::new(0., 0., 1.),
+            tangent: Vec3::new(1., 0., 0.),
+            bitangent: Vec3::new(0., 1., 0.),
+            outgoing: outgoing.normalize(),
+        }
+    }
+
+    #[test]
+    fn microfacet_samples_follow_their_pdf() {
+        assert_samples_follow_pdf(&microfacet(0.5, 0.5, Vec3::new(0., 0., 1.)), 200_000);
+        assert_samples_follow_pdf(&microfacet(0.6, 0.25, Vec3::new(0.4, -0.3, 0.8)), 200_000);
+    }
+
+    #[test]
+    fn smooth_microfacets_stay_finite() {
+        let outgoing = Vec3::new(0.3, 0.2, 0.9).normalize();
+        let bsdf = microfacet(SMOOTH_ROUGHNESS, SMOOTH_ROUGHNESS, outgoing);
+        let mirror = Vec3::new(-outgoing.x, -outgoing.y, outgoing.z);
+        let grazing = Vec3::new(1., 0., 1e-9).normalize();
+        for direction in [mirror, grazing, Vec3::new(0., 0., 1.), outgoing] {
+            let (eval, pdf) = (bsdf.eval(&direction), bsdf.pdf(&direction));
+            assert!(pdf.is_finite() && pdf >= 0., "pdf {} towards {}", pdf, direction);
+            for channel in [eval.r, eval.g, eval.b] {
+                assert!(channel.is_finite() && channel >= 0., "eval {} at {}", eval, direction);
+            }
+        }
+        let mut sampler = IndependentSampler::new(1);
+        let mut near_mirror = 0;
+        for sample in 0..1000 {
+            sampler.start_pixel_sample(0, 0, sample);
+            if let Some(direction) = bsdf.sample(&mut sampler) {
+                assert!(bsdf.pdf(&direction).is_finite());
+                if (direction - mirror).length() < 0.01 {
+                    near_mirror += 1;
+                }
+            }
+        }
+        // GGX has long tails, but nearly everything is reflected like off a mirror
+        assert!(near_mirror > 950, "{} near the mirror direction", near_mirror);
+    }
+
+    #[test]
+    fn conductor_fresnel_at_normal_incidence() {
+        for conductor in [Conductor::Gold, Conductor::Copper, Conductor::Aluminium] {
+            let (eta, k) = conductor.ior();
+            let reflectance = fresnel_conductor(1., eta, k);
+            let expected = |eta: f64, k: f64| {
+                ((eta - 1.).powi(2) + k * k) / ((eta + 1.).powi(2) + k * k)
+            };
+            assert!((reflectance.r - expected(eta.r, k.r)).abs() < 1e-12);
+            assert!((reflectance.g - expected(eta.g, k.g)).abs() < 1e-12);
+            assert!((reflectance.b - expected(eta.b, k.b)).abs() < 1e-12);
+        }
+    }
+
+    /// Mean absolute x and y of directions scattered off the top of a sphere, seen
+    /// from straight above
+    fn highlight_spread(conductor: RoughConductor) -> (f64, f64) {
+        let sphere = sphere(Point3::new(0., 0., 0.), 1.);
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.), 0.);
+        let mut rec = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        rec.material = Arc::new(conductor);
+        let mut sampler = IndependentSampler::new(9);
+        let scatter = conductor.scatter(&ray, &rec, &mut sampler).unwrap();
+        let (mut x, mut y) = (0., 0.);
+        for sample in 0..10_000 {
+            sampler.start_pixel_sample(0, 0, sample);
+            if let Some(sample) = scatter.sample(&mut sampler) {
+                let direction = sample.ray.direction.normalize();
+                x += direction.x.abs() / 10_000.;
+                y += direction.y.abs() / 10_000.;
+            }
+        }
+        (x, y)
+    }
+
+    #[test]
+    fn anisotropic_roughness_follows_the_tangent() {
+        let (eta, k) = Conductor::Aluminium.ior();
+        let along = |tangent| RoughConductor::anisotropic(eta, k, (0.5, 0.05), tangent);
+        let (x, y) = highlight_spread(along(Vec3::new(1., 0., 0.)));
+        assert!(x > 3. * y, "{} {}", x, y);
+        // Only the part in the surface counts
+        let (x, y) = highlight_spread(along(Vec3::new(0., 1., 1.)));
+        assert!(y > 3. * x, "{} {}", x, y);
+        // Parallel to the normal, there's still a valid frame
+        let (x, y) = highlight_spread(along(Vec3::new(0., 0., 1.)));
+        assert!(x.is_finite() && y.is_finite());
+    }
+}
@@ -0,0 +1,415 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::core::{
+    aabb::Aabb,
+    color::Color,
+    grid::DensityGrid,
+    hit::{HitRecord, Hittable},
+    material::{HenyeyGreenstein, Isotropic, Material},
+    ray::Ray,
+    rng::{hash, Pcg32},
+    texture::{SolidColor, Texture},
+    vec3::{Length, Point3, Vec3},
+};
+
+/// Step past a boundary crossing before looking for the next one
+const CROSSING_EPS: f64 = 1e-4;
+
+/// Fog or smoke of uniform density filling a closed `boundary`. Rays scatter off
+/// its particles after exponentially distributed distances (Beer-Lambert), in
+/// every direction alike.
+#[derive(Clone)]
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    /// `-1 / density`, turns the log of a uniform number into a free path length
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+    /// Mixed into the hash of the rays, so media crossed by the same ray draw
+    /// independent free paths
+    salt: u64,
+}
+
+impl ConstantMedium {
+    /// `density` is the chance of scattering per unit of distance, panics unless
+    /// it's positive
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        albedo: Arc<dyn Texture>,
+    ) -> ConstantMedium {
+        ConstantMedium::with_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+
+    /// Scatters with the given material instead, e.g. an `Isotropic` shared with
+    /// other volumes
+    pub fn with_phase_function(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        assert!(density > 0., "Non-positive medium density {}", density);
+        let salt = salt(boundary.bounding_box(), &[density]);
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase_function,
+            salt,
+        }
+    }
+
+    /// The stretches of `(t_min, t_max)` inside the boundary, in order.
+    ///
+    /// Counted from infinitely far behind the origin, crossings of a closed
+    /// boundary alternate between entering and leaving. That finds origins inside
+    /// the volume and every stretch through a concave one.
+    fn stretches(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<(f64, f64)> {
+        let mut stretches = Vec::new();
+        let mut t = f64::NEG_INFINITY;
+        while let Some(enter) = self.boundary.hit(ray, t, f64::INFINITY) {
+            let exit = match self.boundary.hit(ray, enter.t + CROSSING_EPS, f64::INFINITY) {
+                Some(exit) => exit,
+                None => break,
+            };
+            let (start, end) = (enter.t.max(t_min), exit.t.min(t_max));
+            if start < end {
+                stretches.push((start, end));
+            }
+            if exit.t >= t_max {
+                break;
+            }
+            t = exit.t + CROSSING_EPS;
+        }
+        stretches
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let speed = ray.direction.length();
+        // Distance to the next scattering, only counted inside the volume. The same
+        // ray always gets the same one, so asking again with a closer `t_max`
+        // gets an answer that agrees.
+        let u = 1. - ray_rng(ray, self.salt).gen::<f64>();
+        let mut free_path = self.neg_inv_density * u.ln();
+        for (start, end) in self.stretches(ray, t_min, t_max) {
+            let length = (end - start) * speed;
+            if free_path < length {
+                return Some(scattering(ray, start + free_path / speed, &self.phase_function));
+            }
+            // Memoryless, the rest of the path carries on into the next stretch
+            free_path -= length;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+
+    /// Exact, Beer-Lambert over the distance spent inside
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let speed = ray.direction.length();
+        let length: f64 = self
+            .stretches(ray, t_min, t_max)
+            .iter()
+            .map(|(start, end)| (end - start) * speed)
+            .sum();
+        (length / self.neg_inv_density).exp()
+    }
+}
+
+/// Smoke or clouds with the density varying over a voxel grid stretched over a
+/// box. Rays are tracked through it with delta tracking, shadow rays with ratio
+/// tracking.
+#[derive(Clone)]
+pub struct GridMedium {
+    grid: Arc<DensityGrid>,
+    bounds: Aabb,
+    /// Extinction coefficient per unit of density, absorption plus scattering
+    extinction: f64,
+    /// Largest extinction in the volume, the rate tentative collisions are drawn at
+    majorant: f64,
+    phase_function: Arc<dyn Material>,
+    salt: u64,
+}
+
+impl GridMedium {
+    /// The grid's densities are scaled by `absorption` and `scattering` into the
+    /// coefficients per unit of distance, `anisotropy` is the Henyey-Greenstein g
+    /// of the scattering. Panics on negative coefficients, an anisotropy outside
+    /// of (-1, 1) or infinite densities, tracking can't step through those.
+    pub fn new(
+        grid: Arc<DensityGrid>,
+        bounds: Aabb,
+        absorption: f64,
+        scattering: f64,
+        anisotropy: f64,
+    ) -> GridMedium {
+        assert!(
+            absorption >= 0. && scattering >= 0.,
+            "Negative medium coefficients {} and {}",
+            absorption,
+            scattering
+        );
+        assert!(anisotropy.abs() < 1., "Anisotropy {} outside of (-1, 1)", anisotropy);
+        let extinction = absorption + scattering;
+        let majorant = extinction * grid.max_density();
+        assert!(majorant.is_finite(), "Infinite medium extinction {}", majorant);
+        // Absorbed light is accounted for by scattering only the rest
+        let albedo = if extinction > 0. { scattering / extinction } else { 0. };
+        let salt = salt(Some(bounds), &[absorption, scattering, grid.max_density()]);
+        GridMedium {
+            majorant,
+            grid,
+            bounds,
+            extinction,
+            phase_function: Arc::new(HenyeyGreenstein::new(
+                Arc::new(SolidColor::new(Color::new(albedo, albedo, albedo))),
+                anisotropy,
+            )),
+            salt,
+        }
+    }
+
+    fn extinction_at(&self, p: Point3) -> f64 {
+        let size = self.bounds.maximum - self.bounds.minimum;
+        let local = p - self.bounds.minimum;
+        let local = [local.x / size.x, local.y / size.y, local.z / size.z];
+        self.extinction * self.grid.sample(local)
+    }
+
+    /// Tentative collisions along the part of `(t_min, t_max)` in the box, drawn
+    /// at the majorant rate. `visit` gets the `t` of each and the ratio of the
+    /// real extinction there to the majorant, and returns whether to carry on.
+    fn track<F>(&self, ray: &Ray, t_min: f64, t_max: f64, mut visit: F)
+    where
+        F: FnMut(f64, f64) -> bool,
+    {
+        if self.majorant <= 0. {
+            return;
+        }
+        let (start, end) = match self.bounds.clip(ray, t_min, t_max) {
+            Some(interval) => interval,
+            None => return,
+        };
+        let rate = self.majorant * ray.direction.length();
+        let mut rng = ray_rng(ray, self.salt);
+        let mut t = start;
+        loop {
+            t -= (1. - rng.gen::<f64>()).ln() / rate;
+            if t >= end {
+                return;
+            }
+            let ratio = self.extinction_at(ray.at(t)) / self.majorant;
+            if !visit(t, ratio) {
+                return;
+            }
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    /// Delta tracking: a tentative collision is real with the odds of the real
+    /// extinction to the majorant, otherwise the ray carries on
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Seeded apart from the tracking so the two draws aren't correlated
+        let mut rng = ray_rng(ray, !self.salt);
+        let mut collision = None;
+        self.track(ray, t_min, t_max, |t, ratio| {
+            if rng.gen::<f64>() < ratio {
+                collision = Some(t);
+                return false;
+            }
+            true
+        });
+        collision.map(|t| scattering(ray, t, &self.phase_function))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    /// Ratio tracking: every tentative collision keeps the share of light the
+    /// null particles let through, with less noise than testing for a hit
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.;
+        self.track(ray, t_min, t_max, |_, ratio| {
+            transmittance *= 1. - ratio.min(1.);
+            transmittance > 0.
+        });
+        transmittance
+    }
+}
+
+/// A collision at `t` scattering off the medium's particles
+fn scattering(ray: &Ray, t: f64, phase_function: &Arc<dyn Material>) -> HitRecord {
+    HitRecord {
+        p: ray.at(t),
+        t,
+        u: 0.,
+        v: 0.,
+        material: phase_function.clone(),
+        // Arbitrary, phase functions don't use it
+        normal: Vec3::new(1., 0., 0.),
+        front_face: true,
+    }
+}
+
+/// Identifies a medium by where it is and its parameters, only identical volumes
+/// in the same place end up correlated
+fn salt(bbox: Option<Aabb>, parameters: &[f64]) -> u64 {
+    let mut values: Vec<u64> = parameters.iter().map(|value| value.to_bits()).collect();
+    if let Some(bbox) = bbox {
+        for axis in 0..3 {
+            values.push(bbox.minimum[axis].to_bits());
+            values.push(bbox.maximum[axis].to_bits());
+        }
+    }
+    hash(&values)
+}
+
+/// Random numbers that only depend on the ray and `salt`. Hits can't draw from
+/// the path's sampler, this keeps them deterministic all the same.
+fn ray_rng(ray: &Ray, salt: u64) -> Pcg32 {
+    Pcg32::new(hash(&[
+        salt,
+        ray.origin.x.to_bits(),
+        ray.origin.y.to_bits(),
+        ray.origin.z.to_bits(),
+        ray.direction.x.to_bits(),
+        ray.direction.y.to_bits(),
+        ray.direction.z.to_bits(),
+        ray.time.to_bits(),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        color::Color, hittable_list::HittableList, texture::SolidColor, vec3::Point3,
+    };
+    use crate::testing::{sphere, unit_sphere};
+
+    fn fog(boundary: Arc<dyn Hittable>, density: f64) -> ConstantMedium {
+        ConstantMedium::new(boundary, density, Arc::new(SolidColor::new(Color::new(1., 1., 1.))))
+    }
+
+    /// Sends rays along `direction`, each starting a little further back than the last,
+    /// checks where they scatter with `inside` and returns the fraction that did
+    fn scattered_fraction(
+        medium: &ConstantMedium,
+        origin: Point3,
+        direction: Vec3,
+        inside: impl Fn(Point3) -> bool,
+    ) -> f64 {
+        let count = 1000;
+        let mut scattered = 0;
+        for i in 0..count {
+            let ray = Ray::new(origin - 0.001 * i as f64 * direction, direction, 0.);
+            if let Some(rec) = medium.hit(&ray, 0., f64::INFINITY) {
+                assert!(inside(rec.p), "scattered at {}", rec.p);
+                // Asking again with a closer end agrees
+                assert!(medium.hit(&ray, 0., rec.t + 1e-9).is_some());
+                assert!(medium.hit(&ray, 0., rec.t - 1e-9).is_none());
+                scattered += 1;
+            }
+        }
+        scattered as f64 / count as f64
+    }
+
+    #[test]
+    fn constant_transmittance_follows_beer_lambert() {
+        let medium = fog(unit_sphere(), 0.5);
+        // Through the whole diameter, the direction's length doesn't matter
+        let ray = Ray::new(Point3::new(0., 0., -5.), Vec3::new(0., 0., 2.), 0.);
+        let transmittance = medium.transmittance(&ray, 0., f64::INFINITY);
+        assert!((transmittance - (-1f64).exp()).abs() < 1e-9);
+        // Only up to the center
+        let transmittance = medium.transmittance(&ray, 0., 2.5);
+        assert!((transmittance - (-0.5f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn constant_medium_finds_origins_inside() {
+        let medium = fog(unit_sphere(), 2.);
+        let ray = Ray::new(Point3::new(0., 0., 0.), Vec3::new(1., 0., 0.), 0.);
+        let transmittance = medium.transmittance(&ray, 0., f64::INFINITY);
+        assert!((transmittance - (-2f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn constant_medium_scatters_inside_only() {
+        let medium = fog(unit_sphere(), 1.);
+        let origin = Point3::new(0., 0., -5.);
+        let fraction = scattered_fraction(&medium, origin, Vec3::new(0., 0., 1.), |p| {
+            p.length() <= 1. + 1e-9
+        });
+        // About 1 - e⁻² of the rays scatter on their way through the diameter
+        assert!((fraction - (1. - (-2f64).exp())).abs() < 0.06, "{} scattered", fraction);
+    }
+
+    #[test]
+    fn constant_medium_skips_the_gaps_of_concave_boundaries() {
+        let mut spheres: HittableList<dyn Hittable> = HittableList::new();
+        spheres.add(Arc::new(sphere(Point3::new(-3., 0., 0.), 1.)));
+        spheres.add(Arc::new(sphere(Point3::new(3., 0., 0.), 1.)));
+        let medium = fog(Arc::new(spheres), 1.);
+        let origin = Point3::new(-10., 0., 0.);
+        let fraction = scattered_fraction(&medium, origin, Vec3::new(1., 0., 0.), |p| {
+            (p.x.abs() - 3.).abs() <= 1. + 1e-9
+        });
+        // Two diameters of fog, none in between
+        assert!((fraction - (1. - (-4f64).exp())).abs() < 0.06, "{} scattered", fraction);
+
+        let ray = Ray::new(origin, Vec3::new(1., 0., 0.), 0.);
+        assert_eq!(medium.stretches(&ray, 0., f64::INFINITY).len(), 2);
+        let transmittance = medium.transmittance(&ray, 0., f64::INFINITY);
+        assert!((transmittance - (-4f64).exp()).abs() < 1e-9);
+    }
+
+    /// Grid over the unit cube getting denser along x, 0.5 to 4 in its 8 columns
+    fn gradient() -> GridMedium {
+        let grid = DensityGrid::from_fn([8, 2, 2], |x, _, _| 0.5 * (x + 1) as f32);
+        let bounds = Aabb::new(Point3::new(0., 0., 0.), Point3::new(1., 1., 1.));
+        GridMedium::new(Arc::new(grid), bounds, 0.25, 0.75, 0.)
+    }
+
+    #[test]
+    fn grid_tracking_is_unbiased() {
+        let medium = gradient();
+        // Along y through the center of the third column, of density 1.5
+        let expected = (-1.5f64).exp();
+        let (mut ratio, mut delta) = (0., 0.);
+        let count = 4000;
+        for i in 0..count {
+            let origin = Point3::new(2.5 / 8., -1. - 0.001 * i as f64, 0.5);
+            let ray = Ray::new(origin, Vec3::new(0., 1., 0.), 0.);
+            ratio += medium.transmittance(&ray, 0., f64::INFINITY);
+            if medium.hit(&ray, 0., f64::INFINITY).is_none() {
+                delta += 1.;
+            }
+        }
+        let (ratio, delta) = (ratio / count as f64, delta / count as f64);
+        assert!((ratio - expected).abs() < 0.02, "ratio tracking {}", ratio);
+        assert!((delta - expected).abs() < 0.03, "delta tracking {}", delta);
+    }
+
+    #[test]
+    fn grid_medium_is_empty_outside_of_its_box() {
+        let medium = gradient();
+        let ray = Ray::new(Point3::new(2., -1., 0.5), Vec3::new(0., 1., 0.), 0.);
+        assert_eq!(medium.transmittance(&ray, 0., f64::INFINITY), 1.);
+        assert!(medium.hit(&ray, 0., f64::INFINITY).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Infinite medium extinction")]
+    fn grid_medium_rejects_infinite_density() {
+        let grid = DensityGrid::from_fn([1, 1, 1], |_, _, _| f32::INFINITY);
+        let bounds = Aabb::new(Point3::new(0., 0., 0.), Point3::new(1., 1., 1.));
+        GridMedium::new(Arc::new(grid), bounds, 0.5, 0.5, 0.);
+    }
+}
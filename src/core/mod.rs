@@ -1,8 +1,22 @@
+pub mod aabb;
+pub mod background;
+pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod disk;
+pub mod grid;
 pub mod hit;
 pub mod hittable_list;
+pub mod instance;
 pub mod material;
+pub mod medium;
+pub mod perlin;
+pub mod quad;
 pub mod ray;
+pub mod rng;
+pub mod sampler;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
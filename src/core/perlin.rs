@@ -0,0 +1,92 @@
+use rand::seq::SliceRandom;
+
+use crate::core::{
+    rng::Pcg32,
+    vec3::{Point3, Vec3},
+};
+
+const POINT_COUNT: usize = 256;
+
+/// Perlin gradient noise over random unit vectors at the lattice points
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(rng: &mut Pcg32) -> Perlin {
+        Perlin {
+            ranvec: (0..POINT_COUNT)
+                .map(|_| Vec3::random_limit(-1., 1., rng).normalize())
+                .collect(),
+            perm_x: Perlin::generate_perm(rng),
+            perm_y: Perlin::generate_perm(rng),
+            perm_z: Perlin::generate_perm(rng),
+        }
+    }
+
+    fn generate_perm(rng: &mut Pcg32) -> Vec<usize> {
+        let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+        perm.shuffle(rng);
+        perm
+    }
+
+    /// Noise in [-1, 1]
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut c = [[[Vec3::new(0., 0., 0.); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let idx = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.ranvec[idx];
+                }
+            }
+        }
+
+        Perlin::trilinear_interp(&c, u, v, w)
+    }
+
+    /// Sum of `depth` octaves of noise with halving amplitude
+    pub fn turbulence(&self, p: &Point3, depth: usize) -> f64 {
+        let mut accum = 0.;
+        let mut temp_p = *p;
+        let mut weight = 1.;
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.;
+        }
+        accum.abs()
+    }
+
+    fn trilinear_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        // Hermitian smoothing to avoid Mach bands
+        let uu = u * u * (3. - 2. * u);
+        let vv = v * v * (3. - 2. * v);
+        let ww = w * w * (3. - 2. * w);
+
+        let mut accum = 0.;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1. - fi) * (1. - uu))
+                        * (fj * vv + (1. - fj) * (1. - vv))
+                        * (fk * ww + (1. - fk) * (1. - ww))
+                        * corner.dot(weight_v);
+                }
+            }
+        }
+        accum
+    }
+}
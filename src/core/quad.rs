@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::core::{
+    aabb::Aabb,
+    hit::{set_face_normal, HitRecord, Hittable},
+    hittable_list::HittableList,
+    material::Material,
+    ray::Ray,
+    vec3::{Length, Point3, Vec3},
+};
+
+/// Minimum extent of a quad's bounding box, axis-aligned quads are flat
+const BBOX_PADDING: f64 = 1e-4;
+const PARALLEL_EPS: f64 = 1e-8;
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`, its normal is
+/// `u × v`. The hit's UVs run along the edges from 0 at `q` to 1.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+    normal: Vec3,
+    /// Plane offset, `normal · p` for every point of the plane
+    d: f64,
+    /// `n / (n · n)` with the unnormalized normal, maps plane points to edge coordinates
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        Quad {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: normal.dot(q),
+            w: n / n.length_squared(),
+            area: n.length(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(ray.direction);
+        if denom.abs() < PARALLEL_EPS {
+            // Ray parallel to the plane
+            return None;
+        }
+        let t = (self.d - self.normal.dot(ray.origin)) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        // Edge coordinates of the hit point on the plane
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(planar.cross(&self.v));
+        let beta = self.w.dot(self.u.cross(&planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        let (front_face, normal) = set_face_normal(ray, self.normal);
+        Some(HitRecord {
+            p,
+            t,
+            u: alpha,
+            v: beta,
+            front_face,
+            normal,
+            material: self.material.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = Aabb::new(self.q, self.q)
+            .enclose(self.q + self.u)
+            .enclose(self.q + self.v)
+            .enclose(self.q + self.u + self.v);
+        let pad = Vec3::new(BBOX_PADDING, BBOX_PADDING, BBOX_PADDING) / 2.;
+        Some(Aabb::new(bbox.minimum - pad, bbox.maximum + pad))
+    }
+
+    /// Uniform area density converted to solid angle at `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction, 0.), 0.001, f64::INFINITY) {
+            Some(rec) => {
+                let distance_squared = rec.t * rec.t * direction.length_squared();
+                let cosine = (direction.dot(self.normal) / direction.length()).abs();
+                distance_squared / (cosine * self.area)
+            }
+            None => 0.,
+        }
+    }
+
+    fn random(&self, origin: &Point3, u: (f64, f64)) -> Vec3 {
+        let point = self.q + u.0 * self.u + u.1 * self.v;
+        (point - *origin).normalize()
+    }
+}
+
+/// Rectangular box between two opposite corners, the six quads facing outwards
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    sides: HittableList<Quad>,
+}
+
+impl Cuboid {
+    pub fn new(a: Point3, b: Point3, material: Arc<dyn Material>) -> Cuboid {
+        let (min, max) = (a.min(b), a.max(b));
+        let dx = Vec3::new(max.x - min.x, 0., 0.);
+        let dy = Vec3::new(0., max.y - min.y, 0.);
+        let dz = Vec3::new(0., 0., max.z - min.z);
+
+        let mut sides = HittableList::new();
+        let mut side = |q, u, v| sides.add(Arc::new(Quad::new(q, u, v, material.clone())));
+        side(Point3::new(min.x, min.y, max.z), dx, dy); // front
+        side(Point3::new(max.x, min.y, max.z), -dz, dy); // right
+        side(Point3::new(max.x, min.y, min.z), -dx, dy); // back
+        side(Point3::new(min.x, min.y, min.z), dz, dy); // left
+        side(Point3::new(min.x, max.y, max.z), dx, -dz); // top
+        side(Point3::new(min.x, min.y, min.z), dx, dz); // bottom
+        Cuboid { sides }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
+}
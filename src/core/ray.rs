@@ -1,39 +1,177 @@
 use crate::core::{
+    background::Background,
     color::Color,
-    hit::Hittable,
+    hit::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    material::Scatter,
+    sampler::Sampler,
     vec3::{Point3, Vec3},
 };
 
 const BLACK: Color = Color::new(0., 0., 0.);
+/// Bounces every path takes before Russian roulette may end it
+const MIN_BOUNCES: usize = 3;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    /// Instant in the camera's shutter interval the ray travels at
+    pub time: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: Point3, direction: Vec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + t * self.direction
     }
-    pub fn color(&self, world: &dyn Hittable, ray_bounce: usize) -> Color {
-        // If we've exceeded the ray bounce limit, no more light is gathered.
-        // Recursion guard for near objects (cracks)
-        if ray_bounce == 0 {
-            return BLACK;
+    /// Radiance arriving along the ray and the number of rays the path traced.
+    ///
+    /// Diffuse hits also sample the `lights` directly, combined with the scattered
+    /// ray by multiple importance sampling. After `MIN_BOUNCES` paths are ended at
+    /// random with a probability growing as their throughput drops (Russian
+    /// roulette), `max_depth` only caps the rare long survivors.
+    ///
+    /// The `media` are volumes kept out of the world: rays scatter in them when
+    /// they collide before reaching a surface, shadow rays are dimmed by their
+    /// transmittance instead of being blocked.
+    pub fn color(
+        &self,
+        world: &dyn Hittable,
+        media: &HittableList<dyn Hittable>,
+        lights: &HittableList<dyn Hittable>,
+        background: &Background,
+        max_depth: usize,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, usize) {
+        let mut radiance = BLACK;
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut ray = *self;
+        // Density the ray was scattered with, `None` for camera rays and specular
+        // bounces which light sampling can't reproduce
+        let mut scattering_pdf = None;
+
+        for depth in 0..max_depth {
+            let surface = world.hit(&ray, 0.001, f64::INFINITY);
+            let t_max = surface.as_ref().map_or(f64::INFINITY, |record| record.t);
+            let record = match media.hit(&ray, 0.001, t_max).or(surface) {
+                Some(record) => record,
+                None => return (radiance + throughput * background.color(&ray), depth + 1),
+            };
+            let mut emitted = record.material.emitted(&record);
+            if let Some(pdf) = scattering_pdf {
+                // Light sampling at the previous hit could have found this emitter too
+                emitted *= power_heuristic(pdf, lights.pdf_value(&ray.origin, &ray.direction));
+            }
+            radiance += throughput * emitted;
+
+            let scatter = match record.material.scatter(&ray, &record, sampler) {
+                Some(scatter) => scatter,
+                None => return (radiance, depth + 1),
+            };
+            if !scatter.is_specular() && !lights.objects.is_empty() {
+                let direct =
+                    sample_light(ray.time, &record, &scatter, world, media, lights, sampler);
+                radiance += throughput * direct;
+            }
+            let sample = match scatter.sample(sampler) {
+                Some(sample) => sample,
+                None => return (radiance, depth + 1),
+            };
+            throughput = throughput * sample.weight;
+            scattering_pdf = sample.pdf;
+            ray = sample.ray;
+
+            if depth + 1 >= MIN_BOUNCES {
+                // Survivors are weighted up by the odds they beat, keeping the
+                // estimate unbiased
+                let survival = throughput.max_component().min(1.);
+                if sampler.get_1d() >= survival {
+                    return (radiance, depth + 1);
+                }
+                throughput /= survival;
+            }
         }
+        // If we've exceeded the ray bounce limit, no more light is gathered
+        (radiance, max_depth)
+    }
+}
 
-        if let Some(record) = world.hit(self, 0.001, f64::INFINITY) {
-            if let Some(scatter) = record.material.scatter(self, &record) {
-                return scatter.attenuation * scatter.ray.color(world, ray_bounce - 1);
+/// Next event estimation: the light arriving from a direction towards one of the
+/// `lights`, if the shadow ray isn't blocked on the way and as much as the `media`
+/// let through
+fn sample_light(
+    time: f64,
+    record: &HitRecord,
+    scatter: &Scatter,
+    world: &dyn Hittable,
+    media: &HittableList<dyn Hittable>,
+    lights: &HittableList<dyn Hittable>,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let direction = lights.random(&record.p, sampler.get_2d());
+    let light_pdf = lights.pdf_value(&record.p, &direction);
+    let scattering_pdf = scatter.pdf(&direction);
+    if light_pdf == 0. || scattering_pdf == 0. {
+        return BLACK;
+    }
+    // The first thing hit is what's seen, another emitter or an occluder
+    let shadow = Ray::new(record.p, direction, time);
+    match world.hit(&shadow, 0.001, f64::INFINITY) {
+        Some(light) => {
+            let emitted = light.material.emitted(&light);
+            if emitted == BLACK {
+                return BLACK;
             }
-            return BLACK;
+            let weight = power_heuristic(light_pdf, scattering_pdf);
+            let transmittance = media.transmittance(&shadow, 0.001, light.t);
+            weight / light_pdf * transmittance * scatter.eval(&direction) * emitted
+        }
+        None => BLACK,
+    }
+}
+
+/// Veach's power heuristic (β = 2), the weight of a strategy that sampled with
+/// density `pdf` when `other` could have taken the same direction
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (pdf, other) = (pdf * pdf, other * other);
+    pdf / (pdf + other)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::PI, sync::Arc};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::testing::sphere;
+
+    #[test]
+    fn mis_weights_of_both_strategies_sum_to_one() {
+        let mut lights: HittableList<dyn Hittable> = HittableList::new();
+        lights.add(Arc::new(sphere(Point3::new(1., 3., 0.), 0.5)));
+        let origin = Point3::new(0., 0., 0.);
+        let normal = Vec3::new(0., 1., 0.);
+        let mut rng = StdRng::seed_from_u64(17);
+        for _ in 0..100 {
+            let direction = lights.random(&origin, rng.gen());
+            let light_pdf = lights.pdf_value(&origin, &direction);
+            // Cosine weighted scattering off a diffuse floor
+            let scattering_pdf = direction.normalize().dot(normal).max(0.) / PI;
+            assert!(light_pdf > 0. && scattering_pdf > 0.);
+            let light_weight = power_heuristic(light_pdf, scattering_pdf);
+            let scattering_weight = power_heuristic(scattering_pdf, light_pdf);
+            assert!((light_weight + scattering_weight - 1.).abs() < 1e-12);
         }
-        let unit_direction = self.direction.normalize();
-        let t = 0.5 * (unit_direction.y + 1.);
-        (1. - t) * Color::new(1., 1., 1.) + t * Color::new(0.5, 0.7, 1.0)
+        // A strategy that can't take the direction leaves all the weight to the other
+        assert_eq!(power_heuristic(0.3, 0.), 1.);
+        assert_eq!(power_heuristic(0., 0.3), 0.);
     }
 }
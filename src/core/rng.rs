@@ -0,0 +1,81 @@
+use rand::{Error, RngCore, SeedableRng};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+/// PCG-XSH-RR 32, a small and fast generator whose stream only depends on its seed.
+///
+/// Unlike `thread_rng` it is reproducible, samplers seed one per pixel sample from
+/// `hash` so the output doesn't depend on which thread traced it.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: splitmix64(seed).wrapping_add(INCREMENT),
+        };
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(INCREMENT);
+    }
+}
+
+/// Combines e.g. the global seed, pixel coordinates and sample index into one seed
+pub fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0, |acc, &value| splitmix64(acc ^ value))
+}
+
+/// Finalizer of SplitMix64, spreads nearby seeds (e.g. neighbouring pixels) over the state space
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Pcg32 {
+        Pcg32::new(u64::from_le_bytes(seed))
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+
+use crate::core::{
+    rng::{hash, Pcg32},
+    sampler::{sobol::shuffled_scrambled_sobol, Sampler},
+};
+
+/// Side of the tiled blue-noise mask
+const MASK_SIZE: usize = 64;
+/// Width of the Gaussian energy filter of void-and-cluster
+const SIGMA: f64 = 1.5;
+/// Share of the initial binary pattern set to ones
+const INITIAL_DENSITY: f64 = 0.1;
+
+/// Dithered sampling (Georgiev and Fajardo 2016): every pixel walks the same
+/// scrambled Sobol sequence, offset (toroidally) by a blue-noise mask value.
+///
+/// Neighbouring pixels get very different offsets, so at low sample counts the
+/// error looks like fine grain instead of clumps. The sequence is shuffled and
+/// the mask shifted per dimension so dimensions don't correlate.
+#[derive(Debug, Clone)]
+pub struct BlueNoiseSampler {
+    seed: u64,
+    mask: &'static [f64],
+    pixel: (usize, usize),
+    sample: u32,
+    dimension: u64,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> BlueNoiseSampler {
+        BlueNoiseSampler {
+            seed,
+            mask: blue_noise_mask(),
+            pixel: (0, 0),
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Mask value at the pixel, with the mask shifted by `offset`
+    fn mask_value(&self, offset: u64) -> f64 {
+        let (x, y) = self.pixel;
+        let dx = (offset as usize) % MASK_SIZE;
+        let dy = ((offset >> 32) as usize) % MASK_SIZE;
+        self.mask[((y + dy) % MASK_SIZE) * MASK_SIZE + (x + dx) % MASK_SIZE]
+    }
+
+    fn next(&mut self) -> (f64, f64) {
+        // Unlike the Sobol sampler the seed is the same for every pixel
+        let seed = hash(&[self.seed, self.dimension]);
+        self.dimension += 1;
+        let (u, v) = shuffled_scrambled_sobol(self.sample, seed);
+        (
+            (u + self.mask_value(hash(&[seed, 0]))).fract(),
+            (v + self.mask_value(hash(&[seed, 1]))).fract(),
+        )
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize) {
+        self.pixel = (x, y);
+        self.sample = sample as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next().0
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.next()
+    }
+}
+
+/// Tileable blue-noise mask of values in (0, 1), generated once
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let count = MASK_SIZE * MASK_SIZE;
+        void_and_cluster(&mut Pcg32::new(0))
+            .into_iter()
+            .map(|rank| (rank as f64 + 0.5) / count as f64)
+            .collect()
+    })
+}
+
+/// Ulichney's void-and-cluster, ranks every mask pixel so that each threshold of
+/// the ranks gives an evenly spread point set
+fn void_and_cluster(rng: &mut Pcg32) -> Vec<usize> {
+    let count = MASK_SIZE * MASK_SIZE;
+
+    // Toroidal Gaussian by offset, the mask tiles seamlessly
+    let kernel: Vec<f64> = (0..count)
+        .map(|i| {
+            let (dx, dy) = (i % MASK_SIZE, i / MASK_SIZE);
+            let (dx, dy) = (dx.min(MASK_SIZE - dx), dy.min(MASK_SIZE - dy));
+            (-((dx * dx + dy * dy) as f64) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let splat = |energy: &mut [f64], at: usize, sign: f64| {
+        let (px, py) = (at % MASK_SIZE, at / MASK_SIZE);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % MASK_SIZE + MASK_SIZE - px) % MASK_SIZE;
+            let dy = (i / MASK_SIZE + MASK_SIZE - py) % MASK_SIZE;
+            *e += sign * kernel[dy * MASK_SIZE + dx];
+        }
+    };
+    // Densest set pixel or emptiest unset one
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Random initial pattern, relaxed by moving the tightest cluster into the
+    // largest void until that doesn't change anything (or it takes suspiciously long)
+    let ones = (count as f64 * INITIAL_DENSITY) as usize;
+    let mut pattern = vec![false; count];
+    let mut energy = vec![0.; count];
+    let mut placed = 0;
+    while placed < ones {
+        let i = rng.gen_range(0..count);
+        if !pattern[i] {
+            pattern[i] = true;
+            splat(&mut energy, i, 1.);
+            placed += 1;
+        }
+    }
+    for _ in 0..count {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+
+    // Ranks below the initial pattern, removing its tightest clusters first
+    let (mut removing, mut removing_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..ones).rev() {
+        let cluster = tightest_cluster(&removing, &removing_energy);
+        removing[cluster] = false;
+        splat(&mut removing_energy, cluster, -1.);
+        ranks[cluster] = rank;
+    }
+
+    // Ranks above it, filling the largest voids first
+    for rank in ones..count {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.);
+        ranks[void] = rank;
+    }
+    ranks
+}
@@ -0,0 +1,82 @@
+use rand::Rng;
+
+use crate::core::{
+    rng::{hash, Pcg32},
+    sampler::{to_unit, Sampler},
+};
+
+/// Bases of the Halton dimensions, later dimensions fall back to random numbers
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence over the samples of a pixel, one prime base per dimension.
+///
+/// Every pixel and dimension is shifted by a random offset (Cranley-Patterson
+/// rotation) so neighbouring pixels don't share the same pattern.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (u64, u64),
+    sample: usize,
+    dimension: usize,
+    rng: Pcg32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            sample: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed),
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match PRIMES.get(dimension) {
+            Some(&base) => {
+                let (x, y) = self.pixel;
+                let offset = to_unit(hash(&[self.seed, x, y, dimension as u64]) as u32);
+                (radical_inverse(self.sample as u64, base) + offset).fract()
+            }
+            None => self.rng.gen(),
+        }
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the radix point
+fn radical_inverse(mut index: u64, base: u32) -> f64 {
+    let base = base as u64;
+    let inv_base = 1. / base as f64;
+    let mut reversed = 0;
+    let mut inv_base_n = 1.;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base + (index - next * base);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    (reversed as f64 * inv_base_n).min(1. - f64::EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize) {
+        self.pixel = (x as u64, y as u64);
+        self.sample = sample;
+        self.dimension = 0;
+        self.rng = Pcg32::new(hash(&[self.seed, x as u64, y as u64, sample as u64]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
@@ -0,0 +1,36 @@
+use rand::Rng;
+
+use crate::core::{
+    rng::{hash, Pcg32},
+    sampler::Sampler,
+};
+
+/// Every dimension is an independent uniform random number
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            rng: Pcg32::new(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize) {
+        self.rng = Pcg32::new(hash(&[self.seed, x as u64, y as u64, sample as u64]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
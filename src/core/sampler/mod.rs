@@ -0,0 +1,97 @@
+pub mod blue_noise;
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+use blue_noise::BlueNoiseSampler;
+use halton::HaltonSampler;
+use independent::IndependentSampler;
+use sobol::SobolSampler;
+use stratified::StratifiedSampler;
+
+/// Source of the uniform numbers in [0, 1) a pixel sample consumes.
+///
+/// Every sample asks for its dimensions in the same order (pixel position, lens,
+/// then one or two per bounce) so low-discrepancy samplers can distribute each
+/// dimension well over the samples of a pixel. All values only depend on the seed,
+/// the pixel and the sample index.
+pub trait Sampler {
+    /// Starts sample `sample` of the pixel at column `x` and row `y`
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum SamplerKind {
+    /// Uniform random numbers, the slowest to converge
+    Independent,
+    /// Jittered strata, the samples of a pixel cover each dimension evenly
+    Stratified,
+    /// Halton sequence with a random rotation per pixel and dimension
+    Halton,
+    /// Owen-scrambled Sobol sequence, shuffled so dimensions don't correlate
+    Sobol,
+    /// Low-discrepancy sequence offset by a blue-noise mask, leaves the error
+    /// between pixels blue (high frequency) instead of white
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn build(self, seed: u64, samples_per_pixel: usize) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => {
+                Box::new(StratifiedSampler::new(seed, samples_per_pixel))
+            }
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+/// Converts 32 random bits to [0, 1)
+fn to_unit(bits: u32) -> f64 {
+    bits as f64 / (1u64 << 32) as f64
+}
+
+/// Kensler's hashed permutation of `0..len`, `index` maps to a unique position
+/// for every `seed` without storing the permutation
+fn permute(mut index: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    // Cycle walking, permutes within the next power of two until inside `len`
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= seed >> 16;
+        index ^= (index & w) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= seed >> 23;
+        index ^= (index & w) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & w) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & w) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & w) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= w;
+        index ^= index >> 5;
+        if index < len {
+            break;
+        }
+    }
+    index.wrapping_add(seed) % len
+}
@@ -0,0 +1,95 @@
+use crate::core::{
+    rng::hash,
+    sampler::{to_unit, Sampler},
+};
+
+/// Sobol sequence with hash-based Owen scrambling (Burley 2020).
+///
+/// Only the first two Sobol dimensions are used, they are padded into as many
+/// dimensions as needed by shuffling the sample index with a different seed per
+/// dimension, which keeps every 1D and 2D projection well stratified.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u64, u64),
+    sample: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next(&mut self) -> (f64, f64) {
+        let (x, y) = self.pixel;
+        let seed = hash(&[self.seed, x, y, self.dimension]);
+        self.dimension += 1;
+        shuffled_scrambled_sobol(self.sample, seed)
+    }
+}
+
+/// The Owen-scrambled first two Sobol dimensions of the shuffled `index`, `seed`
+/// picks both the shuffle and the scramble
+pub fn shuffled_scrambled_sobol(index: u32, seed: u64) -> (f64, f64) {
+    let index = nested_uniform_scramble(index, seed as u32);
+    (
+        to_unit(nested_uniform_scramble(sobol(index, 0), hash(&[seed, 0]) as u32)),
+        to_unit(nested_uniform_scramble(sobol(index, 1), hash(&[seed, 1]) as u32)),
+    )
+}
+
+/// Dimension 0 (van der Corput) or 1 of the Sobol sequence as 32 fixed point bits
+fn sobol(index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+    // Direction numbers of the primitive polynomial x + 1
+    let mut direction = 1 << 31;
+    let mut result = 0;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    result
+}
+
+/// Laine and Karras' hash, only ever flips bits based on lower bits
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Owen scrambling, flips each bit based on the bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize) {
+        self.pixel = (x as u64, y as u64);
+        self.sample = sample as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next().0
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.next()
+    }
+}
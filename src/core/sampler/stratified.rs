@@ -0,0 +1,135 @@
+use rand::Rng;
+
+use crate::core::{
+    rng::{hash, Pcg32},
+    sampler::{permute, Sampler},
+};
+
+/// Jittered sampling: each dimension is split into one stratum per sample (a grid
+/// for 2D samples) and every sample of a pixel gets its own stratum. Sample counts
+/// without a grid of their own, the primes, get Latin hypercube 2D samples instead,
+/// each axis stratified on its own.
+///
+/// The strata are shuffled per pixel and dimension so dimensions don't correlate.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    seed: u64,
+    samples_per_pixel: usize,
+    /// Grid of exactly one cell per sample for 2D samples, 1 row if there's none
+    columns: usize,
+    rows: usize,
+    pixel: (u64, u64),
+    sample: usize,
+    dimension: u64,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: usize) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        // The factor pair closest to square, e.g. 4x3 for 12 samples
+        let rows = (1..=(samples_per_pixel as f64).sqrt() as usize)
+            .rev()
+            .find(|rows| samples_per_pixel % rows == 0)
+            .unwrap_or(1);
+        StratifiedSampler {
+            seed,
+            samples_per_pixel,
+            columns: samples_per_pixel / rows,
+            rows,
+            pixel: (0, 0),
+            sample: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed),
+        }
+    }
+
+    /// Stratum of the current sample among `count`, the same for no two samples
+    fn stratum(&mut self, count: usize) -> usize {
+        let (x, y) = self.pixel;
+        let permutation = hash(&[self.seed, x, y, self.dimension]) as u32;
+        self.dimension += 1;
+        permute((self.sample % count) as u32, count as u32, permutation) as usize
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample: usize) {
+        self.pixel = (x as u64, y as u64);
+        self.sample = sample;
+        self.dimension = 0;
+        self.rng = Pcg32::new(hash(&[self.seed, x as u64, y as u64, sample as u64]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let count = self.samples_per_pixel;
+        let stratum = self.stratum(count);
+        (stratum as f64 + self.rng.gen::<f64>()) / count as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (columns, rows) = (self.columns, self.rows);
+        if rows == 1 {
+            // A single row would leave the second axis unstratified
+            return (self.get_1d(), self.get_1d());
+        }
+        let stratum = self.stratum(columns * rows);
+        (
+            ((stratum % columns) as f64 + self.rng.gen::<f64>()) / columns as f64,
+            ((stratum / columns) as f64 + self.rng.gen::<f64>()) / rows as f64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The strata of every 2D sample of a pixel, `n` per axis
+    fn strata(samples_per_pixel: usize, n: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut sampler = StratifiedSampler::new(7, samples_per_pixel);
+        (0..samples_per_pixel)
+            .map(|sample| {
+                sampler.start_pixel_sample(3, 5, sample);
+                let (x, y) = sampler.get_2d();
+                ((x * n.0 as f64) as usize, (y * n.1 as f64) as usize)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_grid_cell_gets_a_sample() {
+        for (samples_per_pixel, grid) in [(4, (2, 2)), (6, (3, 2)), (10, (5, 2)), (12, (4, 3))] {
+            let mut cells = strata(samples_per_pixel, grid);
+            cells.sort();
+            cells.dedup();
+            assert_eq!(cells.len(), samples_per_pixel, "{} samples", samples_per_pixel);
+        }
+    }
+
+    #[test]
+    fn prime_counts_stratify_each_axis() {
+        for samples_per_pixel in [2, 7, 13] {
+            let n = (samples_per_pixel, samples_per_pixel);
+            let cells = strata(samples_per_pixel, n);
+            for axis in [|c: &(usize, usize)| c.0, |c: &(usize, usize)| c.1] {
+                let mut strata: Vec<usize> = cells.iter().map(axis).collect();
+                strata.sort();
+                assert_eq!(strata, (0..samples_per_pixel).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn every_1d_stratum_gets_a_sample() {
+        let mut sampler = StratifiedSampler::new(7, 9);
+        let mut strata: Vec<usize> = (0..9)
+            .map(|sample| {
+                sampler.start_pixel_sample(0, 0, sample);
+                (sampler.get_1d() * 9.) as usize
+            })
+            .collect();
+        strata.sort();
+        assert_eq!(strata, (0..9).collect::<Vec<_>>());
+    }
+}
@@ -1,9 +1,11 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 use crate::core::{
+    aabb::Aabb,
     hit::{set_face_normal, HitRecord, Hittable},
     material::Material,
     ray::Ray,
-    vec3::{Length, Vec3},
+    vec3::{Length, Point3, Vec3},
 };
 
 #[derive(Debug, Clone)]
@@ -17,48 +19,194 @@ impl Sphere {
     pub fn new(center: Vec3, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere { center, radius, material }
     }
+
+    /// Spherical coordinates of a point on the unit sphere:
+    /// u in [0, 1] is the angle around the y axis from x = -1,
+    /// v in [0, 1] is the angle from y = -1 to y = +1.
+    pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + std::f64::consts::PI;
+        (
+            phi / (2. * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
+
+    /// Cosine of the half-angle of the cone the sphere subtends from `origin`,
+    /// `None` from inside the sphere where it covers every direction
+    fn cos_theta_max(&self, origin: &Point3) -> Option<f64> {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1. - radius_squared / distance_squared).sqrt())
+    }
+}
+
+/// Nearest intersection in `(t_min, t_max)` with the sphere at `center`, shared by
+/// still and moving spheres
+fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    material: &Arc<dyn Material>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    // (𝐀+𝑡𝐛−𝐂)⋅(𝐀+𝑡𝐛−𝐂)=𝑟2
+    // 𝑡2𝐛⋅𝐛+2𝑡𝐛⋅(𝐀−𝐂)+(𝐀−𝐂)⋅(𝐀−𝐂)−𝑟2=0
+    // (−𝑏±√(𝑏2−4𝑎𝑐))/2𝑎 = −ℎ±√(ℎ2−𝑎𝑐)/𝑎
+
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = oc.dot(ray.direction);
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b.powf(2.) - a * c;
+    if discriminant < 0. {
+        // no solutions, ray doesn't hit the sphere
+        return None;
+    }
+
+    let sqrtd = discriminant.sqrt();
+
+    // Find the nearest root that lies in the acceptable range.
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return None;
+        }
+    }
+
+    let p = ray.at(root);
+
+    // normalized normal
+    let outward_normal = (p - center) / radius;
+    let (front_face, normal) = set_face_normal(ray, outward_normal);
+    // Unit outward normal regardless of the radius sign, hollow spheres keep their mapping
+    let (u, v) = Sphere::get_sphere_uv(&((p - center) / radius.abs()));
+
+    Some(HitRecord {
+        p,
+        t: root,
+        u,
+        v,
+        front_face,
+        normal,
+        material: material.clone(), // copy semantics => new pointer, rc++
+    })
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        // (𝐀+𝑡𝐛−𝐂)⋅(𝐀+𝑡𝐛−𝐂)=𝑟2
-        // 𝑡2𝐛⋅𝐛+2𝑡𝐛⋅(𝐀−𝐂)+(𝐀−𝐂)⋅(𝐀−𝐂)−𝑟2=0
-        // (−𝑏±√(𝑏2−4𝑎𝑐))/2𝑎 = −ℎ±√(ℎ2−𝑎𝑐)/𝑎
-
-        let oc = ray.origin - self.center;
-        let a = ray.direction.length_squared();
-        let half_b = oc.dot(ray.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b.powf(2.) - a * c;
-        if discriminant < 0. {
-            // no solutions, ray doesn't hit the sphere
-            return None;
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Radius can be negative for hollow spheres
+        let r = self.radius.abs();
+        let extent = Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.hit(&Ray::new(*origin, *direction, 0.), 0.001, f64::INFINITY).is_none() {
+            return 0.;
+        }
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => 1. / (2. * PI * (1. - cos_theta_max)),
+            None => 1. / (4. * PI),
+        }
+    }
+
+    /// Uniform direction in the cone the sphere subtends from `origin`
+    fn random(&self, origin: &Point3, u: (f64, f64)) -> Vec3 {
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => (self.center - *origin)
+                .normalize()
+                .from_local(Vec3::sample_in_cone(u, cos_theta_max)),
+            None => Vec3::sample_unit_vector(u),
         }
+    }
+}
 
-        let sqrtd = discriminant.sqrt();
+/// Sphere moving in a straight line from `center0` at `time0` to `center1` at `time1`,
+/// resting at either end outside that interval
+#[derive(Debug, Clone)]
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
+impl MovingSphere {
+    pub fn new(
+        center0: Point3,
+        center1: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
         }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        // Clamped like moving instances, so the sphere never leaves its bounding box
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0., 1.);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(ray.time);
+        hit_sphere(center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    /// Covers the sphere at all times, it doesn't move outside of `time0..time1`
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let extent = Vec3::new(r, r, r);
+        let start = Aabb::new(self.center0 - extent, self.center0 + extent);
+        let end = Aabb::new(self.center1 - extent, self.center1 + extent);
+        Some(start.surrounding(&end))
+    }
+}
 
-        let p = ray.at(root);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::Dielectric;
 
-        // normalized normal
-        let outward_normal = (p - self.center) / self.radius;
-        let (front_face, normal) = set_face_normal(ray, outward_normal);
+    #[test]
+    fn moving_sphere_rests_outside_of_its_interval() {
+        let start = Point3::new(0., 0., 0.);
+        let end = Point3::new(0., 1., 0.);
+        let sphere = MovingSphere::new(start, end, 0., 1., 0.2, Arc::new(Dielectric::new(1.5)));
+        assert_eq!(sphere.center(-1.), start);
+        assert_eq!(sphere.center(0.5), Point3::new(0., 0.5, 0.));
+        assert_eq!(sphere.center(2.), end);
 
-        Some(HitRecord {
-            p,
-            t: root,
-            front_face,
-            normal,
-            material: self.material.clone(), // copy semantics => new pointer, rc++
-        })
+        // A shutter wider than the motion still hits it inside its bounding box
+        let ray = Ray::new(Point3::new(0., 1., -5.), Vec3::new(0., 0., 1.), 3.);
+        let hit = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(sphere.bounding_box().unwrap().hit(&ray, 0.001, f64::INFINITY));
+        assert!((hit.t - 4.8).abs() < 1e-9);
     }
 }
@@ -0,0 +1,311 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::ImageError;
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+use crate::core::{color::Color, perlin::Perlin, rng::Pcg32, vec3::Point3};
+
+/// Color lookup by surface coordinates (u, v) and hit point p
+pub trait Texture: std::fmt::Debug + Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+/// Solid 3D checker pattern, alternating cubes of side `scale`
+#[derive(Debug, Clone)]
+pub struct Checker {
+    pub odd: Arc<dyn Texture>,
+    pub even: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Checker {
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, scale: f64) -> Checker {
+        Checker { odd, even, scale }
+    }
+    pub fn from_colors(odd: Color, even: Color, scale: f64) -> Checker {
+        Checker::new(
+            Arc::new(SolidColor::new(odd)),
+            Arc::new(SolidColor::new(even)),
+            scale,
+        )
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let inv_scale = 1. / self.scale;
+        let cell = (inv_scale * p.x).floor() as i64
+            + (inv_scale * p.y).floor() as i64
+            + (inv_scale * p.z).floor() as i64;
+        if cell.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum NoiseKind {
+    /// Plain Perlin noise
+    Noise,
+    /// Sum of noise octaves
+    Turbulence,
+    /// Turbulence phase-shifting a sine along z
+    Marble,
+}
+
+/// Gray-scale procedural texture driven by Perlin noise
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    pub kind: NoiseKind,
+    /// Frequency of the noise
+    pub scale: f64,
+}
+
+/// Octaves summed for turbulence
+const TURBULENCE_DEPTH: usize = 7;
+
+impl NoiseTexture {
+    pub fn new(kind: NoiseKind, scale: f64, rng: &mut Pcg32) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(rng),
+            kind,
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let white = Color::new(1., 1., 1.);
+        match self.kind {
+            // Map [-1, 1] to [0, 1]
+            NoiseKind::Noise => 0.5 * (1. + self.noise.noise(&(self.scale * *p))) * white,
+            NoiseKind::Turbulence => {
+                self.noise.turbulence(&(self.scale * *p), TURBULENCE_DEPTH) * white
+            }
+            NoiseKind::Marble => {
+                let turbulence = self.noise.turbulence(p, TURBULENCE_DEPTH);
+                0.5 * (1. + (self.scale * p.z + 10. * turbulence).sin()) * white
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+/// How texture coordinates outside of [0, 1] are handled
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum TextureWrap {
+    Clamp,
+    Repeat,
+}
+
+#[derive(Debug)]
+pub struct TextureError {
+    pub path: PathBuf,
+    pub source: ImageError,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Could not load texture {}: {}",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/// Texture sampled from an image file, texels are stored as linear colors
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Row-major, top row first
+    texels: Vec<Color>,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+}
+
+impl ImageTexture {
+    /// Loads a PNG, JPEG or PPM file, the format is guessed from the contents
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        filter: TextureFilter,
+        wrap: TextureWrap,
+    ) -> Result<ImageTexture, TextureError> {
+        let image = image::io::Reader::open(&path)
+            .map_err(ImageError::IoError)
+            .and_then(|reader| reader.with_guessed_format().map_err(ImageError::IoError))
+            .and_then(|reader| reader.decode())
+            .map_err(|source| TextureError {
+                path: path.as_ref().to_path_buf(),
+                source,
+            })?
+            .to_rgb8();
+
+        let texels = image
+            .pixels()
+            .map(|pixel| Color::new_rgb(pixel[0], pixel[1], pixel[2]).srgb_to_linear())
+            .collect();
+        Ok(ImageTexture {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            texels,
+            filter,
+            wrap,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Texel at integer coordinates, which may lie outside the image
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let (x, y) = match self.wrap {
+            TextureWrap::Clamp => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+            TextureWrap::Repeat => (x.rem_euclid(w), y.rem_euclid(h)),
+        };
+        self.texels[(y * w + x) as usize]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        if self.texels.is_empty() {
+            // Cyan for debugging
+            return Color::new(0., 1., 1.);
+        }
+
+        let (u, v) = match self.wrap {
+            TextureWrap::Clamp => (u.clamp(0., 1.), v.clamp(0., 1.)),
+            TextureWrap::Repeat => (u - u.floor(), v - v.floor()),
+        };
+        // Flip v to image coordinates, rows go top to bottom
+        let x = u * self.width as f64;
+        let y = (1. - v) * self.height as f64;
+
+        match self.filter {
+            TextureFilter::Nearest => self.texel(x as i64, y as i64),
+            TextureFilter::Bilinear => {
+                // Texel centers are at half-integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1. - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1. - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1. - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use image::RgbImage;
+
+    use super::*;
+
+    const BLACK: Color = Color::new(0., 0., 0.);
+    const WHITE: Color = Color::new(1., 1., 1.);
+    const RED: Color = Color::new(1., 0., 0.);
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("texture-{}-{}", process::id(), name))
+    }
+
+    /// Black and white on the top row, red and blue below
+    fn checker(filter: TextureFilter, wrap: TextureWrap) -> ImageTexture {
+        let path = temp_path(&format!("{}-{}.png", filter, wrap));
+        let pixels = vec![0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 255];
+        RgbImage::from_raw(2, 2, pixels).unwrap().save(&path).unwrap();
+        let texture = ImageTexture::open(&path, filter, wrap).unwrap();
+        fs::remove_file(&path).unwrap();
+        texture
+    }
+
+    #[test]
+    fn reports_missing_and_corrupt_files() {
+        let missing = temp_path("missing.png");
+        let err = ImageTexture::open(&missing, TextureFilter::Nearest, TextureWrap::Clamp);
+        assert!(matches!(err, Err(TextureError { source: ImageError::IoError(_), .. })));
+
+        let corrupt = temp_path("corrupt.png");
+        fs::write(&corrupt, b"\x89PNG\r\n\x1a\nnot really").unwrap();
+        let err = ImageTexture::open(&corrupt, TextureFilter::Nearest, TextureWrap::Clamp);
+        fs::remove_file(&corrupt).unwrap();
+        assert_eq!(err.unwrap_err().path, corrupt);
+    }
+
+    #[test]
+    fn looks_up_nearest_texels() {
+        let p = Point3::new(0., 0., 0.);
+        let texture = checker(TextureFilter::Nearest, TextureWrap::Clamp);
+        assert_eq!((texture.width(), texture.height()), (2, 2));
+        assert_eq!(texture.value(0.25, 0.75, &p), BLACK);
+        assert_eq!(texture.value(0.75, 0.75, &p), WHITE);
+        assert_eq!(texture.value(0.25, 0.25, &p), RED);
+        assert_eq!(texture.value(-1., 0.75, &p), BLACK);
+        assert_eq!(texture.value(1.75, 0.75, &p), WHITE);
+
+        let texture = checker(TextureFilter::Nearest, TextureWrap::Repeat);
+        assert_eq!(texture.value(1.25, 0.75, &p), BLACK);
+        assert_eq!(texture.value(-0.25, 0.75, &p), WHITE);
+        assert_eq!(texture.value(0.25, -0.75, &p), RED);
+    }
+
+    #[test]
+    fn interpolates_between_texel_centers() {
+        let p = Point3::new(0., 0., 0.);
+        let texture = checker(TextureFilter::Bilinear, TextureWrap::Clamp);
+        assert_eq!(texture.value(0.25, 0.75, &p), BLACK);
+        assert_eq!(texture.value(0.5, 0.5, &p), Color::new(0.5, 0.25, 0.5));
+        // Clamping repeats the edge texel, wrapping blends in the opposite edge
+        assert_eq!(texture.value(0., 0.75, &p), BLACK);
+        let texture = checker(TextureFilter::Bilinear, TextureWrap::Repeat);
+        assert_eq!(texture.value(0., 0.75, &p), Color::new(0.5, 0.5, 0.5));
+    }
+}
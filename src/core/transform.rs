@@ -0,0 +1,637 @@
+use std::ops::Mul;
+
+use crate::core::{
+    aabb::Aabb,
+    ray::Ray,
+    vec3::{Length, Point3, Vec3},
+};
+
+/// Row-major 4x4 matrix acting on column vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        m: [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ],
+    };
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` for singular matrices
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::IDENTITY.m;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1. / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[column][j];
+                        inv[row][j] -= factor * inv[column][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4 { m: inv })
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+/// Affine transform with its inverse kept alongside, so mapping rays into object
+/// space and hits back out never inverts a matrix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    /// `None` if the matrix can't be inverted (e.g. a zero scale)
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::IDENTITY,
+            inverse: Matrix4::IDENTITY,
+        }
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        let matrix = |d: Vec3| Matrix4 {
+            m: [
+                [1., 0., 0., d.x],
+                [0., 1., 0., d.y],
+                [0., 0., 1., d.z],
+                [0., 0., 0., 1.],
+            ],
+        };
+        Transform {
+            matrix: matrix(offset),
+            inverse: matrix(-offset),
+        }
+    }
+
+    /// Scales along the axes, panics on a zero factor
+    pub fn scale(factors: Vec3) -> Transform {
+        assert!(
+            factors.x != 0. && factors.y != 0. && factors.z != 0.,
+            "Zero scale factor in {}",
+            factors
+        );
+        let matrix = |s: Vec3| Matrix4 {
+            m: [
+                [s.x, 0., 0., 0.],
+                [0., s.y, 0., 0.],
+                [0., 0., s.z, 0.],
+                [0., 0., 0., 1.],
+            ],
+        };
+        Transform {
+            matrix: matrix(factors),
+            inverse: matrix(Vec3::new(1. / factors.x, 1. / factors.y, 1. / factors.z)),
+        }
+    }
+
+    /// Counter-clockwise rotation by `degrees` around `axis`, looking down the axis
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let a = axis.normalize();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let matrix = Matrix4 {
+            m: [
+                [
+                    cos + a.x * a.x * (1. - cos),
+                    a.x * a.y * (1. - cos) - a.z * sin,
+                    a.x * a.z * (1. - cos) + a.y * sin,
+                    0.,
+                ],
+                [
+                    a.y * a.x * (1. - cos) + a.z * sin,
+                    cos + a.y * a.y * (1. - cos),
+                    a.y * a.z * (1. - cos) - a.x * sin,
+                    0.,
+                ],
+                [
+                    a.z * a.x * (1. - cos) - a.y * sin,
+                    a.z * a.y * (1. - cos) + a.x * sin,
+                    cos + a.z * a.z * (1. - cos),
+                    0.,
+                ],
+                [0., 0., 0., 1.],
+            ],
+        };
+        // Rotations are orthogonal
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// `self` followed by `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.matrix.m;
+        Point3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.matrix.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Normals go through the inverse transpose to stay perpendicular to the
+    /// surface, the result isn't normalized
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse.m;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    /// The direction isn't normalized, so distances along the ray keep their `t`
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray::new(self.point(ray.origin), self.vector(ray.direction), ray.time)
+    }
+
+    /// Box around the transformed corners of `bbox`
+    pub fn bbox(&self, bbox: &Aabb) -> Aabb {
+        (0..8).fold(Aabb::empty(), |acc, corner| {
+            // Each bit of the corner index picks the minimum or maximum of an axis
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    bbox.minimum[axis]
+                } else {
+                    bbox.maximum[axis]
+                }
+            };
+            acc.enclose(self.point(Point3::new(pick(0), pick(1), pick(2))))
+        })
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+/// Tentative boxes per motion, padded by how far points can stray from the
+/// straight lines between them
+const MOTION_BBOX_STEPS: usize = 16;
+
+/// Motion from one transform to another. Each is split into a translation, a
+/// rotation and the remaining scale and shear, which are blended separately so
+/// rotations stay rigid in between.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    from: Decomposition,
+    to: Decomposition,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, end: Transform) -> AnimatedTransform {
+        let from = Decomposition::new(&start);
+        let mut to = Decomposition::new(&end);
+        // Both signs of a quaternion are the same rotation, take the shorter way
+        if from.rotation.dot(&to.rotation) < 0. {
+            to.rotation = to.rotation.scale(-1.);
+        }
+        AnimatedTransform {
+            start,
+            end,
+            from,
+            to,
+        }
+    }
+
+    /// Whether the transform is the same at every point of the motion
+    pub fn is_static(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Transform at `t` in [0, 1] from the start to the end, `None` if it's
+    /// singular there (e.g. a scale passing through zero)
+    pub fn at(&self, t: f64) -> Option<Transform> {
+        if t <= 0. {
+            return Some(self.start);
+        }
+        if t >= 1. {
+            return Some(self.end);
+        }
+        let translation = self.from.translation + t * (self.to.translation - self.from.translation);
+        let rotation = self.from.rotation.slerp(&self.to.rotation, t).matrix();
+        let mut stretch = self.from.stretch;
+        for (row, other) in stretch.iter_mut().zip(self.to.stretch) {
+            for (value, other) in row.iter_mut().zip(other) {
+                *value += t * (other - *value);
+            }
+        }
+        let linear = mul3(&rotation, &stretch);
+        // S⁻¹ Rᵀ, the inverse of the linear part without a general 4x4 inversion
+        let inverse_linear = mul3(&inverse3(&stretch)?, &transpose3(&rotation));
+        let inverse_translation = -apply3(&inverse_linear, translation);
+        Some(Transform {
+            matrix: affine(&linear, translation),
+            inverse: affine(&inverse_linear, inverse_translation),
+        })
+    }
+
+    /// Box around `bbox` over the whole motion. Boxes at evenly spaced times are
+    /// padded by how far a point can stray from the straight line between two of
+    /// them, bounded through the second derivative of its path.
+    pub fn bbox(&self, bbox: &Aabb) -> Aabb {
+        let ends = self.start.bbox(bbox).surrounding(&self.end.bbox(bbox));
+        if self.from.rotation == self.to.rotation {
+            // Without rotation every point moves in a straight line
+            return ends;
+        }
+        let angle = 2. * self.from.rotation.dot(&self.to.rotation).clamp(-1., 1.).acos();
+        // Largest stretched corner and the largest change of one, norms are convex
+        // so both are found at the ends of the motion and the corners of the box
+        let (mut reach, mut drift) = (0., 0.);
+        for corner in 0..8 {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    bbox.minimum[axis]
+                } else {
+                    bbox.maximum[axis]
+                }
+            };
+            let p = Vec3::new(pick(0), pick(1), pick(2));
+            let (from, to) = (apply3(&self.from.stretch, p), apply3(&self.to.stretch, p));
+            reach = f64::max(reach, from.length().max(to.length()));
+            drift = f64::max(drift, (to - from).length());
+        }
+        // Linear interpolation over a step `h` is off by at most h² / 8 |p''|, and
+        // |p''| <= θ² |S x| + 2θ |S' x| for a rotation by θ at constant speed
+        let step = 1. / MOTION_BBOX_STEPS as f64;
+        let pad = step * step / 8. * (angle * angle * reach + 2. * angle * drift);
+        let mut swept = ends;
+        for i in 1..MOTION_BBOX_STEPS {
+            if let Some(transform) = self.at(i as f64 * step) {
+                swept = swept.surrounding(&transform.bbox(bbox));
+            }
+        }
+        let pad = Vec3::new(pad, pad, pad);
+        Aabb::new(swept.minimum - pad, swept.maximum + pad)
+    }
+}
+
+/// `T R S` split of an affine transform: translation, rotation and a stretch
+/// with the scale and shear, found by polar decomposition
+#[derive(Debug, Clone, Copy)]
+struct Decomposition {
+    translation: Vec3,
+    rotation: Quaternion,
+    stretch: [[f64; 3]; 3],
+}
+
+impl Decomposition {
+    fn new(transform: &Transform) -> Decomposition {
+        let m = &transform.matrix.m;
+        let linear = [
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ];
+        // Averaging with the inverse transpose converges to the closest orthogonal
+        // matrix. Transforms are invertible, so every step is too.
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let inverse = match inverse3(&rotation) {
+                Some(inverse) => transpose3(&inverse),
+                None => break,
+            };
+            let mut change: f64 = 0.;
+            for (row, inverse) in rotation.iter_mut().zip(inverse) {
+                for (value, inverse) in row.iter_mut().zip(inverse) {
+                    let next = 0.5 * (*value + inverse);
+                    change = change.max((next - *value).abs());
+                    *value = next;
+                }
+            }
+            if change < 1e-12 {
+                break;
+            }
+        }
+        // Mirroring is left to the stretch, quaternions only hold proper rotations
+        if det3(&rotation) < 0. {
+            rotation = rotation.map(|row| row.map(|value| -value));
+        }
+        Decomposition {
+            translation: Vec3::new(m[0][3], m[1][3], m[2][3]),
+            stretch: mul3(&transpose3(&rotation), &linear),
+            rotation: Quaternion::from_matrix(&rotation),
+        }
+    }
+}
+
+/// Unit quaternion `w + xi + yj + zk` for a rotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quaternion {
+    w: f64,
+    v: Vec3,
+}
+
+impl Quaternion {
+    /// From a rotation matrix, picking the largest component to divide by
+    fn from_matrix(m: &[[f64; 3]; 3]) -> Quaternion {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0. {
+            let s = 2. * (trace + 1.).sqrt();
+            Quaternion {
+                w: s / 4.,
+                v: Vec3::new(m[2][1] - m[1][2], m[0][2] - m[2][0], m[1][0] - m[0][1]) / s,
+            }
+        } else {
+            // The largest diagonal entry names the largest of x, y and z
+            let i = (0..3).max_by(|&i, &j| m[i][i].total_cmp(&m[j][j])).unwrap();
+            let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+            let s = 2. * (m[i][i] - m[j][j] - m[k][k] + 1.).sqrt();
+            let mut v = [0.; 3];
+            v[i] = s / 4.;
+            v[j] = (m[j][i] + m[i][j]) / s;
+            v[k] = (m[k][i] + m[i][k]) / s;
+            Quaternion {
+                w: (m[k][j] - m[j][k]) / s,
+                v: Vec3::new(v[0], v[1], v[2]),
+            }
+        };
+        q.scale(1. / q.dot(&q).sqrt())
+    }
+
+    fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    fn scale(&self, factor: f64) -> Quaternion {
+        Quaternion {
+            w: self.w * factor,
+            v: self.v * factor,
+        }
+    }
+
+    /// Rotation at constant speed from `self` at `t = 0` to `other` at `t = 1`
+    fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let cos = self.dot(other).clamp(-1., 1.);
+        let blend = |a: f64, b: f64| Quaternion {
+            w: a * self.w + b * other.w,
+            v: a * self.v + b * other.v,
+        };
+        let q = if cos > 0.9995 {
+            // Nearly the same rotation, the sines below would lose precision
+            blend(1. - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            blend(((1. - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        q.scale(1. / q.dot(&q).sqrt())
+    }
+
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        let (w, x, y, z) = (self.w, self.v.x, self.v.y, self.v.z);
+        [
+            [1. - 2. * (y * y + z * z), 2. * (x * y - w * z), 2. * (x * z + w * y)],
+            [2. * (x * y + w * z), 1. - 2. * (x * x + z * z), 2. * (y * z - w * x)],
+            [2. * (x * z - w * y), 2. * (y * z + w * x), 1. - 2. * (x * x + y * y)],
+        ]
+    }
+}
+
+fn affine(linear: &[[f64; 3]; 3], translation: Vec3) -> Matrix4 {
+    let row = |i: usize| [linear[i][0], linear[i][1], linear[i][2], translation[i]];
+    Matrix4 {
+        m: [row(0), row(1), row(2), [0., 0., 0., 1.]],
+    }
+}
+
+fn apply3(m: &[[f64; 3]; 3], v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    )
+}
+
+fn mul3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut m = [[0.; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn transpose3(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut t = [[0.; 3]; 3];
+    for (i, row) in t.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    t
+}
+
+fn det3(m: &[[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Adjugate over the determinant, `None` for singular matrices
+fn inverse3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let det = det3(m);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    // Cofactors of the transpose, each from the two rows and columns after it
+    let mut inv = [[0.; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            *value = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+        }
+    }
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::placed;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn assert_inverse(transform: &Transform) {
+        let product = *transform.matrix() * transform.inverse;
+        for (i, row) in product.m.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((value - expected).abs() < 1e-9, "{:?}", product);
+            }
+        }
+    }
+
+    #[test]
+    fn inverts_matrices() {
+        let transform = placed();
+        assert_inverse(&transform);
+        let inverse = transform.matrix().inverse().unwrap();
+        assert_eq!(Transform::new(*transform.matrix()).unwrap().inverse, inverse);
+        let p = Point3::new(0.3, -4., 2.);
+        assert_close(transform.inverse().point(transform.point(p)), p);
+    }
+
+    #[test]
+    fn rejects_singular_matrices() {
+        let mut m = Matrix4::IDENTITY;
+        m.m[1] = [2., 0., 4., 1.];
+        m.m[2] = [1., 0., 2., 0.];
+        assert!(m.inverse().is_none());
+        assert!(Transform::new(m).is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let transform = placed();
+        let (tangent, normal) = (Vec3::new(1., 1., 0.), Vec3::new(1., -1., 0.));
+        let dot = transform.vector(tangent).dot(transform.normal(normal));
+        assert!(dot.abs() < 1e-9);
+    }
+
+    #[test]
+    fn animated_transform_keeps_its_ends() {
+        let (start, end) = (placed(), Transform::translate(Vec3::new(0., 3., 0.)));
+        let motion = AnimatedTransform::new(start, end);
+        assert_eq!(motion.at(-1.), Some(start));
+        assert_eq!(motion.at(2.), Some(end));
+        let p = Point3::new(1., 2., 3.);
+        assert_close(motion.at(1e-12).unwrap().point(p), start.point(p));
+        assert_close(motion.at(1. - 1e-12).unwrap().point(p), end.point(p));
+        assert_inverse(&motion.at(0.3).unwrap());
+    }
+
+    #[test]
+    fn animated_rotation_stays_rigid() {
+        let axis = Vec3::new(0., 1., 0.);
+        let offset = Transform::translate(Vec3::new(2., 0., 0.));
+        let motion = AnimatedTransform::new(
+            Transform::rotate(axis, 0.).then(&offset),
+            Transform::rotate(axis, 120.).then(&offset),
+        );
+        let p = Point3::new(1., 1., 1.);
+        let expected = Transform::rotate(axis, 60.).then(&offset);
+        assert_close(motion.at(0.5).unwrap().point(p), expected.point(p));
+    }
+
+    #[test]
+    fn animated_transform_keeps_mirroring() {
+        let mirror = Transform::scale(Vec3::new(-1., 2., 1.));
+        let motion = AnimatedTransform::new(
+            mirror,
+            mirror.then(&Transform::rotate(Vec3::new(0., 0., 1.), 90.)),
+        );
+        let p = Point3::new(1., 0., 0.);
+        let expected = mirror.then(&Transform::rotate(Vec3::new(0., 0., 1.), 45.));
+        assert_close(motion.at(0.5).unwrap().point(p), expected.point(p));
+    }
+
+    #[test]
+    fn animated_bbox_encloses_the_motion() {
+        let motion = AnimatedTransform::new(
+            Transform::translate(Vec3::new(3., 0., 0.)),
+            Transform::translate(Vec3::new(3., 0., 0.))
+                .then(&Transform::rotate(Vec3::new(0., 0., 1.), 180.)),
+        );
+        let bbox = Aabb::new(Point3::new(-2., -0.1, -0.1), Point3::new(2., 0.1, 0.1));
+        let swept = motion.bbox(&bbox);
+        for i in 0..=100 {
+            let moved = motion.at(i as f64 / 100.).unwrap().bbox(&bbox);
+            for axis in 0..3 {
+                assert!(swept.minimum[axis] <= moved.minimum[axis] + 1e-9);
+                assert!(swept.maximum[axis] >= moved.maximum[axis] - 1e-9);
+            }
+        }
+        // The rod spins about its center on the way, upright half way through
+        assert!(swept.maximum.y >= 2. - 1e-9 && swept.minimum.y <= -2. + 1e-9);
+    }
+}
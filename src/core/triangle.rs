@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use crate::core::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    hit::{set_face_normal, HitRecord, Hittable},
+    hittable_list::HittableList,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+/// Minimum extent of a triangle's bounding box, axis-aligned triangles are flat
+const BBOX_PADDING: f64 = 1e-4;
+const DET_EPS: f64 = 1e-12;
+
+/// Indices of one triangle corner into the vertex buffers of its mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// Triangles sharing vertex buffers and a material
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<[MeshVertex; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        uvs: Vec<(f64, f64)>,
+        normals: Vec<Vec3>,
+        faces: Vec<[MeshVertex; 3]>,
+        material: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        TriangleMesh {
+            positions,
+            uvs,
+            normals,
+            faces,
+            material,
+        }
+    }
+
+    pub fn triangles(self: &Arc<Self>) -> HittableList<Triangle> {
+        let mut list = HittableList::new();
+        for index in 0..self.faces.len() {
+            list.add(Arc::new(Triangle {
+                mesh: self.clone(),
+                index,
+            }));
+        }
+        list
+    }
+
+    /// The mesh's triangles behind a BVH, what should be added to the world
+    pub fn into_bvh(self) -> BvhNode<Triangle> {
+        BvhNode::new(Arc::new(self).triangles())
+    }
+}
+
+/// One face of a `TriangleMesh`
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl Triangle {
+    /// A stand-alone triangle, without normals or UVs
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, material: Arc<dyn Material>) -> Triangle {
+        let corner = |position| MeshVertex {
+            position,
+            uv: None,
+            normal: None,
+        };
+        let mesh = TriangleMesh::new(
+            vec![p0, p1, p2],
+            Vec::new(),
+            Vec::new(),
+            vec![[corner(0), corner(1), corner(2)]],
+            material,
+        );
+        Triangle {
+            mesh: Arc::new(mesh),
+            index: 0,
+        }
+    }
+
+    fn vertices(&self) -> [Point3; 3] {
+        let face = &self.mesh.faces[self.index];
+        [
+            self.mesh.positions[face[0].position],
+            self.mesh.positions[face[1].position],
+            self.mesh.positions[face[2].position],
+        ]
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Möller–Trumbore, solves 𝐎+𝑡𝐃 = (1−𝑏1−𝑏2)𝐏0 + 𝑏1𝐏1 + 𝑏2𝐏2 via Cramer's rule
+        let [p0, p1, p2] = self.vertices();
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+
+        let pvec = ray.direction.cross(&e2);
+        let det = e1.dot(pvec);
+        if det.abs() < DET_EPS {
+            // Ray parallel to the triangle's plane
+            return None;
+        }
+        let inv_det = 1. / det;
+
+        let tvec = ray.origin - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&e1);
+        let b2 = ray.direction.dot(qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+
+        let t = e2.dot(qvec) * inv_det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+        let b0 = 1. - b1 - b2;
+
+        let face = &self.mesh.faces[self.index];
+        let geometric_normal = e1.cross(&e2).normalize();
+        let (front_face, normal) = set_face_normal(ray, geometric_normal);
+
+        // Interpolated shading normal, kept on the same side as the geometric one
+        let normal = match (face[0].normal, face[1].normal, face[2].normal) {
+            (Some(n0), Some(n1), Some(n2)) => {
+                let shading = (b0 * self.mesh.normals[n0]
+                    + b1 * self.mesh.normals[n1]
+                    + b2 * self.mesh.normals[n2])
+                    .normalize();
+                if shading.dot(normal) < 0. {
+                    -shading
+                } else {
+                    shading
+                }
+            }
+            _ => normal,
+        };
+
+        let (u, v) = match (face[0].uv, face[1].uv, face[2].uv) {
+            (Some(t0), Some(t1), Some(t2)) => {
+                let (uv0, uv1, uv2) = (self.mesh.uvs[t0], self.mesh.uvs[t1], self.mesh.uvs[t2]);
+                (
+                    b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                    b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+                )
+            }
+            _ => (b1, b2),
+        };
+
+        Some(HitRecord {
+            p: ray.at(t),
+            t,
+            u,
+            v,
+            front_face,
+            normal,
+            material: self.mesh.material.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = self.vertices();
+        let bbox = Aabb::new(p0.min(p1).min(p2), p0.max(p1).max(p2));
+        let pad = Vec3::new(BBOX_PADDING, BBOX_PADDING, BBOX_PADDING) / 2.;
+        Some(Aabb::new(bbox.minimum - pad, bbox.maximum + pad))
+    }
+}
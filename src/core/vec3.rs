@@ -1,4 +1,4 @@
-use rand::{random, thread_rng, Rng};
+use rand::Rng;
 
 pub trait Length {
     fn length(self) -> f64;
@@ -34,47 +34,88 @@ impl Vec3 {
     pub fn normalize(self) -> Vec3 {
         self / self.length()
     }
-    pub fn random() -> Vec3 {
-        Vec3::new(random::<f64>(), random::<f64>(), random::<f64>())
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Vec3 {
+        Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
     }
-    pub fn random_limit(min: f64, max: f64) -> Vec3 {
-        let mut rng = thread_rng();
+    pub fn random_limit<R: Rng + ?Sized>(min: f64, max: f64, rng: &mut R) -> Vec3 {
         Vec3::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
             rng.gen_range(min..max),
         )
     }
-    pub fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let point = Vec3::random_limit(-1., 1.);
-            if point.length_squared() < 1. {
-                return point;
-            }
-        }
-    }
-    pub fn random_unit_vector() -> Vec3 {
-        Vec3::random_in_unit_sphere().normalize()
-    }
-    pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-        let in_unit_sphere = Vec3::random_in_unit_sphere();
-        if in_unit_sphere.dot(*normal) > 0. {
+    /// Uniform point in the unit ball from a 2D sample for the direction and a 1D one for the radius.
+    ///
+    /// The `sample_*` functions warp uniform samples instead of rejecting random
+    /// points, so stratified samples stay stratified.
+    pub fn sample_in_unit_sphere(u: (f64, f64), r: f64) -> Vec3 {
+        r.cbrt() * Vec3::sample_unit_vector(u)
+    }
+    /// Uniform direction on the unit sphere
+    pub fn sample_unit_vector(u: (f64, f64)) -> Vec3 {
+        let z = 1. - 2. * u.0;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * std::f64::consts::PI * u.1;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+    /// Uniform direction on the hemisphere around `normal`
+    pub fn sample_in_hemisphere(normal: &Vec3, u: (f64, f64)) -> Vec3 {
+        let on_unit_sphere = Vec3::sample_unit_vector(u);
+        if on_unit_sphere.dot(*normal) > 0. {
             // In the same hemisphere as the normal
-            in_unit_sphere
+            on_unit_sphere
         } else {
-            -in_unit_sphere
+            -on_unit_sphere
         }
     }
-    pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = thread_rng();
-        loop {
-            let x = rng.gen_range(-1.0..1.);
-            let y = rng.gen_range(-1.0..1.);
-            let p = Vec3::new(x, y, 0.);
-            if p.length_squared() < 1. {
-                return p;
-            }
+    /// Uniform point in the unit disk (z = 0), Shirley and Chiu's concentric mapping
+    pub fn sample_in_unit_disk(u: (f64, f64)) -> Vec3 {
+        let (a, b) = (2. * u.0 - 1., 2. * u.1 - 1.);
+        if a == 0. && b == 0. {
+            return Vec3::new(0., 0., 0.);
         }
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, std::f64::consts::FRAC_PI_4 * (b / a))
+        } else {
+            (b, std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (a / b))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.)
+    }
+    /// Cosine-weighted direction on the hemisphere around +z (Malley's method),
+    /// uniform points of the unit disk lifted onto the hemisphere
+    pub fn sample_cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+        let disk = Vec3::sample_in_unit_disk(u);
+        let z = (1. - disk.x * disk.x - disk.y * disk.y).max(0.).sqrt();
+        Vec3::new(disk.x, disk.y, z)
+    }
+    /// Uniform direction in the cone around +z whose half-angle has cosine `cos_theta_max`
+    pub fn sample_in_cone(u: (f64, f64), cos_theta_max: f64) -> Vec3 {
+        let z = 1. + u.1 * (cos_theta_max - 1.);
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * std::f64::consts::PI * u.0;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+    /// Two unit vectors completing the unit vector to an orthonormal basis
+    /// (Duff et al. 2017), without branching on a helper axis
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = 1f64.copysign(self.z);
+        let a = -1. / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vec3::new(1. + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+    /// Transforms a vector given in the basis whose z axis is the unit vector `self`
+    pub fn from_local(&self, local: Vec3) -> Vec3 {
+        let (s, t) = self.orthonormal_basis();
+        local.x * s + local.y * t + local.z * *self
+    }
+    pub fn min(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+    pub fn max(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
     }
     pub fn sqrt(&self) -> Vec3 {
         Vec3::new(self.x.sqrt(), self.y.sqrt(), self.z.sqrt())
@@ -106,12 +147,30 @@ impl Length for Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(v: [f64; 3]) -> Vec3 {
+        Vec3::new(v[0], v[1], v[2])
+    }
+}
+
 impl std::fmt::Display for Vec3 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{{ x: {}, y: {}, z: {} }}", self.x, self.y, self.z)
     }
 }
 
+impl std::ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of bounds: {}", axis),
+        }
+    }
+}
+
 impl std::ops::Neg for Vec3 {
     type Output = Self;
     fn neg(self) -> Vec3 {
@@ -0,0 +1,7 @@
+pub mod core;
+pub mod render;
+pub mod scene;
+pub mod settings;
+pub mod tonemap;
+#[cfg(test)]
+mod testing;
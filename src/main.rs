@@ -1,12 +1,10 @@
 #![allow(clippy::needless_return)]
 
-use std::{io::prelude::*, time::Instant};
+use std::time::Instant;
 
-use rand::random;
-use rayon::prelude::*;
-
-mod core;
-use crate::core::{color::Color, material::DiffuseMethod};
+use wort::core::{bvh::BvhNode, material::DiffuseMethod};
+use wort::render::Renderer;
+use wort::scene::{file::SceneDescription, Scene};
 
 mod sinks;
 use sinks::Sink;
@@ -14,19 +12,7 @@ use sinks::Sink;
 mod arg;
 use arg::{parse_arguments, Args};
 
-mod scene;
-use scene::{generate_scene, random_scene, get_camera};
-
-// Image
-const ASPECT_RATIO: f64 = 3. / 2.;
-const IMAGE_WIDTH: usize = 600;
-const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as usize;
-const SAMPLES_PER_PIXEL: usize = 10;
-const MAX_RAY_BOUNCE_DEPTH: usize = 50;
-
 fn main() -> std::io::Result<()> {
-    let mut stdout = std::io::stdout();
-
     // Don't run the program rls!
     if cfg!(debug_assertions) {
         return Ok(());
@@ -36,55 +22,71 @@ fn main() -> std::io::Result<()> {
     let Args {
         output,
         filename,
-        mut diffuse_method,
+        heatmap,
+        diffuse_method,
         verbose,
+        settings,
+        scene,
+        builtin,
+        background,
+        exr_precision,
+        tone_mapper,
     } = parse_arguments();
 
-    // TODO: make arg into a param struct
-    let mut trace = |diffuse_method: &mut DiffuseMethod| -> std::io::Result<std::vec::Vec<_>> {
-        let start = Instant::now();
-
-        let world = random_scene(diffuse_method);
-        let camera = get_camera();
-
-        // Render
-        let mut pixels: Vec<Color> = Vec::with_capacity(IMAGE_WIDTH * IMAGE_HEIGHT);
-        for j in (0..IMAGE_HEIGHT).rev() {
-            if verbose {
-                eprintln!("Scanlines remaining: {}", j);
-                stdout.flush()?;
-            }
-
-            let par_iter = (0..IMAGE_WIDTH).into_par_iter().map(|i| {
-                let mut pixel_color =
-                    (0..SAMPLES_PER_PIXEL)
-                        .into_iter()
-                        .fold(Color::new(0., 0., 0.), |acc, _| {
-                            let u = (i as f64 + random::<f64>()) / (IMAGE_WIDTH - 1) as f64;
-                            let v = (j as f64 + random::<f64>()) / (IMAGE_HEIGHT - 1) as f64;
-                            let ray = camera.get_ray(u, v);
-                            acc + ray.color(&world, MAX_RAY_BOUNCE_DEPTH)
-                        });
-                // Divide the color by the number of samples to get the average
-                pixel_color /= SAMPLES_PER_PIXEL as f64;
-                // Gamma-correct for gamma=2.0.
-                pixel_color.sqrt()
-            });
-
-            let mut line_pixels: Vec<_> = par_iter.collect();
-            pixels.append(&mut line_pixels);
-        }
-
-        let duration = start.elapsed();
-        eprintln!("Ray tracing took {:.3}s", duration.as_secs_f64());
-        Ok(pixels)
+    let scene_description = scene.map(|path| {
+        SceneDescription::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    });
+
+    let build = |mut diffuse_method: DiffuseMethod| -> Renderer {
+        let Scene {
+            world,
+            media,
+            lights,
+            camera,
+            background: scene_background,
+        } = match &scene_description {
+            Some(description) => description.build(diffuse_method, &settings),
+            None => builtin.build(&mut diffuse_method, &settings),
+        };
+        Renderer::new(
+            Box::new(BvhNode::new(world)),
+            media,
+            lights,
+            camera,
+            background.unwrap_or(scene_background),
+            settings,
+        )
     };
 
     match output {
-        Sink::File => {
-            let pixels = trace(&mut diffuse_method).unwrap();
-            sinks::file::write_to_file(filename.unwrap(), &pixels)
+        Sink::Window => {
+            sinks::window::draw_in_window(build, diffuse_method, &settings, tone_mapper)
+        }
+        _ => {
+            let renderer = build(diffuse_method);
+            let start = Instant::now();
+            let film = renderer.trace(0..settings.samples_per_pixel, verbose);
+            eprintln!("Ray tracing took {:.3}s", start.elapsed().as_secs_f64());
+            eprintln!("Average path length: {:.2}", film.average_path_length());
+            if settings.noise_threshold.is_some() {
+                eprintln!("Average samples per pixel: {:.2}", film.average_samples());
+            }
+            if let Some(heatmap) = heatmap {
+                let format = Sink::from_filename(&heatmap).unwrap();
+                let mut colors = film.sample_heatmap();
+                if format.is_hdr() {
+                    colors.iter_mut().for_each(|color| *color = color.srgb_to_linear());
+                }
+                sinks::file::write_to_file(heatmap, &colors, &settings, format, exr_precision)?;
+            }
+            let mut pixels = film.pixels();
+            if !output.is_hdr() {
+                pixels = tone_mapper.apply_all(&pixels);
+            }
+            sinks::file::write_to_file(filename.unwrap(), &pixels, &settings, output, exr_precision)
         }
-        Sink::Window => sinks::window::draw_in_window(trace, &mut diffuse_method),
     }
 }
@@ -0,0 +1,374 @@
+use std::f64::consts::PI;
+
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+use crate::core::color::Color;
+use crate::render::tile::Tile;
+
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum FilterKind {
+    /// Plain average of the samples inside the radius
+    Box,
+    /// Linear falloff to zero at the radius
+    Tent,
+    /// Gaussian with a standard deviation of a third of the radius, shifted to
+    /// reach zero at the radius
+    Gaussian,
+    /// Mitchell-Netravali cubic with B = C = 1/3, slightly sharpening
+    Mitchell,
+    /// Sinc windowed by a wider sinc, the sharpest with the most ringing
+    Lanczos,
+}
+
+impl FilterKind {
+    /// Radius in pixels the filter is usually used with
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell | FilterKind::Lanczos => 2.,
+        }
+    }
+}
+
+/// Separable pixel reconstruction filter
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// Extent in pixels from the sample in both directions
+    pub radius: f64,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Filter {
+        Filter { kind, radius }
+    }
+
+    /// Weight of a sample `dx`, `dy` pixels away from a pixel center
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let (x, r) = (x.abs(), self.radius);
+        if x > r {
+            return 0.;
+        }
+        match self.kind {
+            FilterKind::Box => 1.,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian => {
+                let sigma = r / 3.;
+                let gaussian = |x: f64| (-x * x / (2. * sigma * sigma)).exp();
+                gaussian(x) - gaussian(r)
+            }
+            FilterKind::Mitchell => mitchell(2. * x / r),
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+/// Mitchell-Netravali cubic on [0, 2] with B = C = 1/3
+fn mitchell(x: f64) -> f64 {
+    let (b, c) = (1. / 3., 1. / 3.);
+    let value = if x < 1. {
+        (12. - 9. * b - 6. * c) * x.powi(3) + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b)
+    } else {
+        (-b - 6. * c) * x.powi(3)
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c)
+    };
+    value / 6.
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Weighted sample sums of a tile's neighbourhood, filters reach over the tile's edge
+#[derive(Debug, Clone)]
+pub struct FilmTile {
+    /// The render tile the samples were taken in
+    pub tile: Tile,
+    /// Pixels the samples were splatted to, the tile grown by the filter radius
+    pub bounds: Tile,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+    /// Samples taken in each pixel of the tile
+    sample_counts: Vec<usize>,
+    /// Rays traced by all the tile's paths, shadow rays aside
+    path_lengths: usize,
+    filter: Filter,
+}
+
+impl FilmTile {
+    pub fn new(tile: Tile, filter: Filter, image_width: usize, image_height: usize) -> FilmTile {
+        // Samples lie inside the tile, so pixel centers up to the radius outside of it
+        let margin = (filter.radius + 0.5).floor() as usize;
+        let (x, y) = (tile.x.saturating_sub(margin), tile.y.saturating_sub(margin));
+        let bounds = Tile {
+            x,
+            y,
+            width: (tile.x + tile.width + margin).min(image_width) - x,
+            height: (tile.y + tile.height + margin).min(image_height) - y,
+        };
+        let pixel_count = bounds.width * bounds.height;
+        FilmTile {
+            tile,
+            bounds,
+            sums: vec![Color::new(0., 0., 0.); pixel_count],
+            weights: vec![0.; pixel_count],
+            sample_counts: vec![0; tile.width * tile.height],
+            path_lengths: 0,
+            filter,
+        }
+    }
+
+    /// Splats a sample at continuous image coordinates (pixel `x` covers `[x, x + 1)`,
+    /// rows from the top) to every pixel whose center is within the filter radius
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        let radius = self.filter.radius;
+        let bounds = self.bounds;
+        // Pixel centers are at half-integers
+        let x0 = (x - 0.5 - radius).ceil().max(bounds.x as f64) as usize;
+        let x1 = ((x - 0.5 + radius).floor() as usize + 1).min(bounds.x + bounds.width);
+        let y0 = (y - 0.5 - radius).ceil().max(bounds.y as f64) as usize;
+        let y1 = ((y - 0.5 + radius).floor() as usize + 1).min(bounds.y + bounds.height);
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let weight = self
+                    .filter
+                    .evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight != 0. {
+                    let index = (py - bounds.y) * bounds.width + px - bounds.x;
+                    self.sums[index] += weight * color;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    pub fn add_path_length(&mut self, length: usize) {
+        self.path_lengths += length;
+    }
+
+    /// Records that `count` samples were taken in the tile's pixel at column `x`, row `y`
+    pub fn set_sample_count(&mut self, x: usize, y: usize, count: usize) {
+        self.sample_counts[(y - self.tile.y) * self.tile.width + x - self.tile.x] = count;
+    }
+}
+
+/// The image as normalized sums of filter-weighted samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+    sample_counts: Vec<usize>,
+    path_lengths: usize,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            sums: vec![Color::new(0., 0., 0.); width * height],
+            weights: vec![0.; width * height],
+            sample_counts: vec![0; width * height],
+            path_lengths: 0,
+        }
+    }
+
+    pub fn merge(&mut self, film_tile: &FilmTile) {
+        let indices = film_tile.bounds.indices(self.width);
+        for (index, (sum, weight)) in indices.zip(film_tile.sums.iter().zip(&film_tile.weights)) {
+            self.sums[index] += *sum;
+            self.weights[index] += weight;
+        }
+        let indices = film_tile.tile.indices(self.width);
+        for (index, count) in indices.zip(&film_tile.sample_counts) {
+            self.sample_counts[index] += count;
+        }
+        self.path_lengths += film_tile.path_lengths;
+    }
+
+    /// Weighted average of the samples around the pixel. Negative lobes (Mitchell,
+    /// Lanczos) can push it below zero at hard edges, which is clipped.
+    pub fn pixel(&self, index: usize) -> Color {
+        let weight = self.weights[index];
+        if weight <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+        let color = self.sums[index] / weight;
+        Color::new(color.r.max(0.), color.g.max(0.), color.b.max(0.))
+    }
+
+    /// Whether any sample was splatted to the pixel yet
+    pub fn has_samples(&self, index: usize) -> bool {
+        self.weights[index] != 0.
+    }
+
+    pub fn pixels(&self) -> Vec<Color> {
+        (0..self.sums.len()).map(|index| self.pixel(index)).collect()
+    }
+
+    /// Mean samples taken per pixel
+    pub fn average_samples(&self) -> f64 {
+        self.sample_counts.iter().sum::<usize>() as f64 / self.sample_counts.len() as f64
+    }
+
+    /// Mean rays traced per sample, shadow rays aside
+    pub fn average_path_length(&self) -> f64 {
+        self.path_lengths as f64 / self.sample_counts.iter().sum::<usize>().max(1) as f64
+    }
+
+    /// Samples taken per pixel as display-ready colors, from dark blue for the
+    /// fewest samples to yellow for the most
+    pub fn sample_heatmap(&self) -> Vec<Color> {
+        let min = self.sample_counts.iter().copied().min().unwrap_or(0);
+        let max = self.sample_counts.iter().copied().max().unwrap_or(0);
+        let range = (max - min).max(1) as f64;
+        self.sample_counts
+            .iter()
+            .map(|&count| viridis((count - min) as f64 / range))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.sums.iter_mut().for_each(|sum| *sum = Color::new(0., 0., 0.));
+        self.weights.iter_mut().for_each(|weight| *weight = 0.);
+        self.sample_counts.iter_mut().for_each(|count| *count = 0);
+        self.path_lengths = 0;
+    }
+}
+
+/// Piecewise linear approximation of the viridis colormap for `t` in [0, 1], sRGB encoded
+fn viridis(t: f64) -> Color {
+    const STOPS: [(u8, u8, u8); 5] = [
+        (68, 1, 84),
+        (59, 82, 139),
+        (33, 145, 140),
+        (94, 201, 98),
+        (253, 231, 37),
+    ];
+    let position = t.clamp(0., 1.) * (STOPS.len() - 1) as f64;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let (from, to) = (STOPS[index], STOPS[index + 1]);
+    let (from, to) = (
+        Color::new_rgb(from.0, from.1, from.2),
+        Color::new_rgb(to.0, to.1, to.2),
+    );
+    let t = position - index as f64;
+    (1. - t) * from + t * to
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn filters_peak_in_the_center_and_vanish_at_their_radius() {
+        for kind in KINDS {
+            let filter = Filter::new(kind, kind.default_radius());
+            let center = filter.evaluate(0., 0.);
+            assert!(center > 0., "{}", kind);
+            for x in [0.1, 0.25, 0.4, 0.6, 1., 1.3, 1.9] {
+                let weight = filter.evaluate(x, 0.);
+                assert!(weight <= center, "{} at {}", kind, x);
+                assert_eq!(weight, filter.evaluate(-x, 0.), "{} isn't symmetric", kind);
+                // Separable
+                let product = weight * filter.evaluate(0., 0.3) / center;
+                assert!((filter.evaluate(x, 0.3) - product).abs() < 1e-12);
+            }
+            let r = filter.radius;
+            assert!(filter.evaluate(r + 1e-9, 0.) == 0. && filter.evaluate(0., -r - 1e-9) == 0.);
+            if kind != FilterKind::Box {
+                assert!(filter.evaluate(r, 0.).abs() < 1e-12, "{}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn mitchell_is_continuous() {
+        assert!((mitchell(0.) - 16. / 18.).abs() < 1e-12);
+        assert!((mitchell(1. - 1e-9) - mitchell(1.)).abs() < 1e-6);
+        assert!(mitchell(2.).abs() < 1e-12);
+        // The negative lobe that sharpens
+        assert!(mitchell(1.5) < 0.);
+    }
+
+    #[test]
+    fn lanczos_vanishes_at_whole_pixels() {
+        let filter = Filter::new(FilterKind::Lanczos, 2.);
+        assert_eq!(filter.evaluate(0., 0.), 1.);
+        assert!(filter.evaluate(1., 0.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constant_images_stay_constant() {
+        let color = Color::new(0.2, 0.5, 1.);
+        let (width, height) = (12, 8);
+        let tile = Tile {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        for kind in KINDS {
+            let filter = Filter::new(kind, kind.default_radius());
+            let mut film_tile = FilmTile::new(tile, filter, width, height);
+            for index in 0..width * height * 16 {
+                let (x, y) = ((index % (width * 4)) as f64, (index / (width * 4)) as f64);
+                film_tile.add_sample((x + 0.5) / 4., (y + 0.5) / 4., color);
+            }
+            let mut film = Film::new(width, height);
+            film.merge(&film_tile);
+            for index in 0..width * height {
+                let error = film.pixel(index) - color;
+                let error = error.r.abs().max(error.g.abs()).max(error.b.abs());
+                assert!(error < 1e-9, "{} is off by {}", kind, error);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_reach_over_the_tile_edge() {
+        let tile = Tile {
+            x: 4,
+            y: 0,
+            width: 4,
+            height: 4,
+        };
+        let mut film_tile = FilmTile::new(tile, Filter::new(FilterKind::Tent, 1.), 12, 4);
+        assert_eq!((film_tile.bounds.x, film_tile.bounds.width), (3, 6));
+        film_tile.add_sample(4.2, 2.5, Color::new(1., 1., 1.));
+        let mut film = Film::new(12, 4);
+        film.merge(&film_tile);
+        assert!(film.has_samples(2 * 12 + 3));
+        assert!(!film.has_samples(2 * 12 + 5));
+    }
+}
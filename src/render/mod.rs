@@ -0,0 +1,270 @@
+pub mod film;
+pub mod tile;
+
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use rayon::prelude::*;
+
+use crate::core::{
+    background::Background, camera::Camera, hit::Hittable, hittable_list::HittableList,
+};
+use crate::settings::RenderSettings;
+use film::{Film, FilmTile};
+use tile::{tiles, Tile};
+
+/// A built scene ready to be traced
+pub struct Renderer {
+    pub world: Box<dyn Hittable>,
+    /// Volumes traced apart from the world, see `Ray::color`
+    pub media: HittableList<dyn Hittable>,
+    /// Emitters sampled directly, also part of the world
+    pub lights: HittableList<dyn Hittable>,
+    pub camera: Camera,
+    pub background: Background,
+    pub settings: RenderSettings,
+}
+
+impl Renderer {
+    pub fn new(
+        world: Box<dyn Hittable>,
+        media: HittableList<dyn Hittable>,
+        lights: HittableList<dyn Hittable>,
+        camera: Camera,
+        background: Background,
+        settings: RenderSettings,
+    ) -> Renderer {
+        Renderer {
+            world,
+            media,
+            lights,
+            camera,
+            background,
+            settings,
+        }
+    }
+
+    /// Traces the `samples` of every pixel into a film of filtered linear radiance.
+    /// Samples are identified by their index, so tracing `0..4` and then `4..8`
+    /// gives the samples of tracing `0..8`.
+    ///
+    /// With a noise threshold pixels keep being sampled past the range until
+    /// they converge, the variance only covers the samples of this call.
+    pub fn trace(&self, samples: Range<usize>, verbose: bool) -> Film {
+        let film_tiles = Mutex::new(Vec::new());
+        let remaining = AtomicUsize::new(tiles(&self.settings).len());
+        self.trace_tiles(samples, |film_tile| {
+            film_tiles.lock().unwrap().push(film_tile);
+            let remaining = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+            if verbose {
+                eprintln!("Tiles remaining: {}", remaining);
+            }
+        });
+
+        // Merged in a fixed order, the sums of overlapping tiles don't depend on
+        // which thread finished first
+        let mut film_tiles = film_tiles.into_inner().unwrap();
+        film_tiles.sort_by_key(|film_tile| (film_tile.tile.y, film_tile.tile.x));
+        let mut film = Film::new(self.settings.image_width, self.settings.image_height);
+        for film_tile in &film_tiles {
+            film.merge(film_tile);
+        }
+        film
+    }
+
+    /// Traces the image tile by tile in the configured order, `on_tile` gets the
+    /// splatted samples of every finished tile from the worker thread
+    pub fn trace_tiles<F>(&self, samples: Range<usize>, on_tile: F)
+    where
+        F: Fn(FilmTile) + Sync,
+    {
+        // Bridging keeps the tiles handed out in order, idle threads steal the next one
+        tiles(&self.settings)
+            .into_iter()
+            .par_bridge()
+            .for_each(|tile| on_tile(self.trace_tile(tile, samples.clone())));
+    }
+
+    fn trace_tile(&self, tile: Tile, samples: Range<usize>) -> FilmTile {
+        let RenderSettings {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            seed,
+            sampler,
+            filter,
+            noise_threshold,
+            max_samples_per_pixel,
+            ..
+        } = self.settings;
+        // Adaptive sampling can take samples up to the cap, they all need strata of
+        // their own
+        let sample_count = match noise_threshold {
+            Some(_) => max_samples_per_pixel.max(samples_per_pixel),
+            None => samples_per_pixel,
+        };
+        let mut sampler = sampler.build(seed, sample_count);
+        let mut film_tile = FilmTile::new(tile, filter, image_width, image_height);
+
+        for index in tile.indices(image_width) {
+            let (i, row) = (index % image_width, index / image_width);
+            // Rows count from the top, the camera's v from the bottom
+            let j = image_height - 1 - row;
+            let mut stats = PixelStats::default();
+            for sample in samples.start.. {
+                let converged = match noise_threshold {
+                    Some(threshold) => {
+                        sample >= max_samples_per_pixel || stats.relative_error() < threshold
+                    }
+                    None => true,
+                };
+                if sample >= samples.end && converged {
+                    break;
+                }
+
+                // Only depends on the pixel and sample, not on the thread tracing it
+                sampler.start_pixel_sample(i, row, sample);
+                let (du, dv) = sampler.get_2d();
+                let u = (i as f64 + du) / (image_width - 1) as f64;
+                let v = (j as f64 + dv) / (image_height - 1) as f64;
+                let ray = self.camera.get_ray(u, v, sampler.as_mut());
+                let (color, path_length) = ray.color(
+                    self.world.as_ref(),
+                    &self.media,
+                    &self.lights,
+                    &self.background,
+                    max_depth,
+                    sampler.as_mut(),
+                );
+                // The linear radiance is kept and tone mapped for display sinks
+                film_tile.add_sample(i as f64 + du, (row + 1) as f64 - dv, color);
+                film_tile.add_path_length(path_length);
+                stats.add(color.luminance());
+            }
+            film_tile.set_sample_count(i, row, stats.count);
+        }
+        film_tile
+    }
+}
+
+/// Running mean and variance of a pixel's sample luminance (Welford's algorithm)
+#[derive(Debug, Default)]
+struct PixelStats {
+    count: usize,
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+}
+
+/// Below this mean luminance the relative error is taken against it instead, so
+/// near black pixels don't sample up to the cap chasing invisible noise
+const DARK_LUMINANCE: f64 = 0.01;
+
+impl PixelStats {
+    fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Standard error of the mean relative to the mean
+    fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.max(DARK_LUMINANCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+    use crate::core::{
+        color::Color, hittable_list::HittableList, material::DiffuseMethod, vec3::Point3,
+    };
+    use crate::scene::generate_scene;
+    use crate::testing::settings;
+    use tile::TileOrder;
+
+    fn renderer(
+        world: Box<dyn Hittable>,
+        background: Background,
+        settings: RenderSettings,
+    ) -> Renderer {
+        let camera = Camera::new(
+            Point3::new(0., 0.5, 2.),
+            Point3::new(0., 0., -1.),
+            Point3::new(0., 1., 0.),
+            40.,
+            settings.aspect_ratio(),
+            0.1,
+            3.,
+        );
+        let (media, lights) = (HittableList::new(), HittableList::new());
+        Renderer::new(world, media, lights, camera, background, settings)
+    }
+
+    fn render(threads: usize) -> Film {
+        let mut settings = settings(24, 16, 4, TileOrder::Spiral);
+        settings.max_depth = 8;
+        settings.seed = 7;
+        let world = Box::new(generate_scene(&mut DiffuseMethod::Lambert));
+        let renderer = renderer(world, Background::default(), settings);
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| renderer.trace(0..4, false))
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        let single = render(1);
+        assert!(single.pixels().iter().any(|pixel| pixel.r > 0.));
+        assert_eq!(single, render(4));
+    }
+
+    #[test]
+    fn pixel_stats_match_two_passes() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let samples: Vec<f64> = (0..1000).map(|_| rng.gen_range(0.0..10.0)).collect();
+        let mut stats = PixelStats::default();
+        samples.iter().for_each(|&x| stats.add(x));
+
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.);
+        assert!((stats.mean - mean).abs() < 1e-12);
+        assert!((stats.relative_error() - (variance / n).sqrt() / mean).abs() < 1e-12);
+    }
+
+    /// Average samples taken per pixel with a noise threshold, up to 32 samples
+    fn adaptive_samples(background: Background, threshold: f64) -> f64 {
+        let mut settings = settings(8, 6, 4, TileOrder::Spiral);
+        settings.samples_per_pixel = 4;
+        settings.noise_threshold = Some(threshold);
+        settings.max_samples_per_pixel = 32;
+        let world = Box::new(HittableList::<dyn Hittable>::new());
+        renderer(world, background, settings).trace(0..4, false).average_samples()
+    }
+
+    #[test]
+    fn constant_pixels_stop_at_the_samples_per_pixel() {
+        let gray = Background::Solid(Color::new(0.5, 0.5, 0.5));
+        assert_eq!(adaptive_samples(gray, 0.01), 4.);
+    }
+
+    #[test]
+    fn noisy_pixels_stop_at_the_cap() {
+        // The sky changes within every pixel, nothing gets below this threshold
+        assert_eq!(adaptive_samples(Background::Gradient, 1e-12), 32.);
+    }
+}
@@ -0,0 +1,164 @@
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+use crate::settings::RenderSettings;
+
+/// Order in which tiles are handed to the thread pool
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum TileOrder {
+    /// Left to right, top to bottom
+    Scanline,
+    /// Outwards from the center tile, the usually interesting part shows up first
+    Spiral,
+    /// Along a Hilbert curve, neighbouring tiles are rendered close in time
+    Hilbert,
+}
+
+/// Rectangle of pixels, `y` counts rows from the top of the image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Image buffer indices of the tile's pixels, row by row
+    pub fn indices(&self, image_width: usize) -> impl Iterator<Item = usize> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| y * image_width + x))
+    }
+}
+
+/// Splits the image into tiles of at most `tile_size` squared, edge tiles are cropped
+pub fn tiles(settings: &RenderSettings) -> Vec<Tile> {
+    let size = settings.tile_size;
+    let columns = settings.image_width.div_ceil(size);
+    let rows = settings.image_height.div_ceil(size);
+    let tile = |(column, row): (usize, usize)| Tile {
+        x: column * size,
+        y: row * size,
+        width: size.min(settings.image_width - column * size),
+        height: size.min(settings.image_height - row * size),
+    };
+
+    let order: Vec<(usize, usize)> = match settings.tile_order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            (0..n * n)
+                .map(|d| hilbert(n, d))
+                .filter(|&(column, row)| column < columns && row < rows)
+                .collect()
+        }
+    };
+    order.into_iter().map(tile).collect()
+}
+
+/// Square spiral around the center of the grid, positions outside it are skipped
+fn spiral(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let mut order = Vec::with_capacity(columns * rows);
+    let (mut x, mut y) = (((columns - 1) / 2) as isize, ((rows - 1) / 2) as isize);
+    // Right, down, left, up with the run length growing every second turn
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut run = 1;
+    let mut turn = 0;
+    while order.len() < columns * rows {
+        let (dx, dy) = directions[turn % 4];
+        for _ in 0..run {
+            if 0 <= x && x < columns as isize && 0 <= y && y < rows as isize {
+                order.push((x as usize, y as usize));
+            }
+            x += dx;
+            y += dy;
+        }
+        turn += 1;
+        if turn % 2 == 0 {
+            run += 1;
+        }
+    }
+    order
+}
+
+/// Position of distance `d` along the Hilbert curve filling an `n`×`n` grid, `n` a power of two
+fn hilbert(n: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // Rotate the quadrant so the sub-curves connect
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::settings;
+
+    #[test]
+    fn every_order_covers_each_pixel_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for (width, height, size) in [(64, 64, 16), (100, 37, 16), (7, 90, 32), (1, 1, 8)] {
+                let mut covered = vec![0; width * height];
+                for tile in tiles(&settings(width, height, size, order)) {
+                    assert!(tile.width <= size && tile.height <= size);
+                    for index in tile.indices(width) {
+                        covered[index] += 1;
+                    }
+                }
+                assert!(covered.iter().all(|&count| count == 1), "{} {}x{}", order, width, height);
+            }
+        }
+    }
+
+    #[test]
+    fn scanline_goes_row_by_row() {
+        let order: Vec<_> = tiles(&settings(48, 32, 16, TileOrder::Scanline))
+            .iter()
+            .map(|tile| (tile.x, tile.y))
+            .collect();
+        assert_eq!(order, [(0, 0), (16, 0), (32, 0), (0, 16), (16, 16), (32, 16)]);
+    }
+
+    #[test]
+    fn spiral_starts_in_the_center() {
+        let order = tiles(&settings(80, 48, 16, TileOrder::Spiral));
+        assert_eq!((order[0].x, order[0].y), (32, 16));
+        assert_eq!((order[1].x, order[1].y), (48, 16));
+    }
+
+    #[test]
+    fn hilbert_steps_between_neighbours() {
+        let order = tiles(&settings(128, 128, 16, TileOrder::Hilbert));
+        assert_eq!(order.len(), 64);
+        for pair in order.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 16);
+        }
+    }
+
+    #[test]
+    fn edge_tiles_are_cropped() {
+        let order = tiles(&settings(40, 20, 16, TileOrder::Scanline));
+        let last = order.last().unwrap();
+        assert_eq!((last.x, last.y, last.width, last.height), (32, 16, 8, 4));
+    }
+}
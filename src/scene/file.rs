@@ -0,0 +1,1110 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use serde::Deserialize;
+
+use crate::core::{
+    aabb::Aabb,
+    background::Background,
+    camera::Camera,
+    disk::Disk,
+    grid::{DensityGrid, GridError},
+    hit::Hittable,
+    hittable_list::HittableList,
+    instance::Instance,
+    material::{
+        Conductor, Dielectric, DiffuseLight, DiffuseMethod, Isotropic, Lambertian, Material,
+        Metal, RoughConductor,
+    },
+    medium::{ConstantMedium, GridMedium},
+    quad::{Cuboid, Quad},
+    rng::Pcg32,
+    sphere::Sphere,
+    texture::{
+        Checker, ImageTexture, NoiseKind, NoiseTexture, SolidColor, Texture, TextureError,
+        TextureFilter, TextureWrap,
+    },
+    transform::Transform,
+    triangle::TriangleMesh,
+    vec3::{Length, Point3, Vec3},
+};
+use crate::scene::{
+    obj::{load_obj, ObjError},
+    Scene,
+};
+use crate::settings::RenderSettings;
+
+/// Declarative scene, deserialized from a TOML file such as:
+///
+/// ```toml
+/// [camera]
+/// lookfrom = [13.0, 2.0, 3.0]
+/// lookat = [0.0, 0.0, 0.0]
+/// vfov = 20.0
+/// aperture = 0.1
+///
+/// # `gradient` (default), `black`, "#RRGGBB" or [r, g, b]
+/// background = "black"
+///
+/// [textures.checker]
+/// type = "checker"
+/// odd = [0.2, 0.3, 0.1]
+/// even = [0.9, 0.9, 0.9]
+/// scale = 0.3
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// # a texture name or a solid [r, g, b] color
+/// albedo = "checker"
+///
+/// [[objects]]
+/// type = "sphere"
+/// center = [0.0, -1000.0, 0.0]
+/// radius = 1000.0
+/// material = "ground"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub background: Option<BackgroundDescription>,
+    #[serde(default)]
+    pub textures: HashMap<String, TextureDescription>,
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    pub volumes: Vec<VolumeDescription>,
+    /// Decoded image textures by texture name, loaded once when parsing
+    #[serde(skip)]
+    images: HashMap<String, Arc<ImageTexture>>,
+    /// Loaded meshes by object index
+    #[serde(skip)]
+    meshes: HashMap<usize, Arc<TriangleMesh>>,
+    /// Loaded density grids by their path in the file
+    #[serde(skip)]
+    grids: HashMap<PathBuf, Arc<DensityGrid>>,
+}
+
+/// Parameters of `Camera::new` and the shutter, the aspect ratio comes from the
+/// render settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub lookfrom: [f64; 3],
+    pub lookat: [f64; 3],
+    #[serde(default = "default_vup")]
+    pub vup: [f64; 3],
+    /// Vertical FOV in degrees
+    pub vfov: f64,
+    #[serde(default)]
+    pub aperture: f64,
+    /// Defaults to the distance between `lookfrom` and `lookat`
+    pub focus_dist: Option<f64>,
+    /// Times the shutter opens and closes, objects with a `motion` move in between
+    #[serde(default)]
+    pub shutter: [f64; 2],
+}
+
+fn default_vup() -> [f64; 3] {
+    [0., 1., 0.]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BackgroundDescription {
+    Named(String),
+    Color([f64; 3]),
+}
+
+impl BackgroundDescription {
+    pub fn build(&self) -> Result<Background, String> {
+        match self {
+            BackgroundDescription::Named(name) => Background::from_str(name),
+            BackgroundDescription::Color(color) => Ok(Background::Solid((*color).into())),
+        }
+    }
+}
+
+/// Either a solid color or the name of a texture in the `textures` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TextureRef {
+    Color([f64; 3]),
+    Named(String),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseKindDescription {
+    Noise,
+    Turbulence,
+    Marble,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterDescription {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapDescription {
+    Clamp,
+    Repeat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        odd: TextureRef,
+        even: TextureRef,
+        #[serde(default = "default_checker_scale")]
+        scale: f64,
+    },
+    Noise {
+        #[serde(default = "default_noise_kind")]
+        kind: NoiseKindDescription,
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+    },
+    /// PNG, JPEG or PPM file, relative paths are resolved against the scene file
+    Image {
+        path: PathBuf,
+        #[serde(default = "default_filter")]
+        filter: FilterDescription,
+        #[serde(default = "default_wrap")]
+        wrap: WrapDescription,
+    },
+}
+
+fn default_filter() -> FilterDescription {
+    FilterDescription::Bilinear
+}
+
+fn default_wrap() -> WrapDescription {
+    WrapDescription::Repeat
+}
+
+fn default_checker_scale() -> f64 {
+    1.
+}
+
+fn default_noise_kind() -> NoiseKindDescription {
+    NoiseKindDescription::Noise
+}
+
+fn default_noise_scale() -> f64 {
+    1.
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: TextureRef,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ir: f64,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+    },
+    /// GGX microfacet metal, e.g. `conductor = "gold"` or
+    /// `conductor = { eta = [..], k = [..] }` with `roughness = 0.2`, or one
+    /// roughness along the `tangent` (a world space direction, x by default) and
+    /// one across it
+    RoughConductor {
+        conductor: ConductorDescription,
+        #[serde(default)]
+        roughness: RoughnessDescription,
+        #[serde(default = "default_tangent")]
+        tangent: [f64; 3],
+    },
+    /// Turns the objects using it into fog of this `density` filling their surface,
+    /// which has to be closed
+    Isotropic {
+        albedo: TextureRef,
+        density: f64,
+    },
+}
+
+/// A measured metal or the complex index of refraction per channel
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum ConductorDescription {
+    Preset(ConductorPreset),
+    Ior { eta: [f64; 3], k: [f64; 3] },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum RoughnessDescription {
+    Isotropic(f64),
+    Anisotropic([f64; 2]),
+}
+
+impl RoughnessDescription {
+    /// Along the tangent and the bitangent
+    pub fn values(&self) -> [f64; 2] {
+        match *self {
+            RoughnessDescription::Isotropic(roughness) => [roughness, roughness],
+            RoughnessDescription::Anisotropic(roughness) => roughness,
+        }
+    }
+}
+
+fn default_tangent() -> [f64; 3] {
+    [1., 0., 0.]
+}
+
+impl Default for RoughnessDescription {
+    fn default() -> Self {
+        RoughnessDescription::Isotropic(0.)
+    }
+}
+
+/// Objects are placed by an optional `transform`, e.g.
+/// `transform = { scale = 2.0, rotate = [0.0, 45.0, 0.0], translate = [1.0, 0.0, 0.0] }`.
+/// An optional `motion` is the transform reached when the camera's shutter closes,
+/// the object moves there from `transform` while it's open.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+        #[serde(default)]
+        transform: Option<TransformDescription>,
+        #[serde(default)]
+        motion: Option<TransformDescription>,
+    },
+    /// Parallelogram with the corner `q` and edges `u` and `v`
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+        #[serde(default)]
+        transform: Option<TransformDescription>,
+        #[serde(default)]
+        motion: Option<TransformDescription>,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+        #[serde(default)]
+        transform: Option<TransformDescription>,
+        #[serde(default)]
+        motion: Option<TransformDescription>,
+    },
+    /// Axis-aligned box between two opposite corners
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+        #[serde(default)]
+        transform: Option<TransformDescription>,
+        #[serde(default)]
+        motion: Option<TransformDescription>,
+    },
+    /// Wavefront OBJ file, relative paths are resolved against the scene file.
+    /// Objects with the same file and material share one copy of the mesh.
+    Mesh {
+        path: PathBuf,
+        material: String,
+        #[serde(default)]
+        transform: Option<TransformDescription>,
+        #[serde(default)]
+        motion: Option<TransformDescription>,
+    },
+}
+
+impl ObjectDescription {
+    pub fn material(&self) -> &str {
+        match self {
+            ObjectDescription::Sphere { material, .. } => material,
+            ObjectDescription::Quad { material, .. } => material,
+            ObjectDescription::Disk { material, .. } => material,
+            ObjectDescription::Box { material, .. } => material,
+            ObjectDescription::Mesh { material, .. } => material,
+        }
+    }
+
+    pub fn transform(&self) -> Option<&TransformDescription> {
+        match self {
+            ObjectDescription::Sphere { transform, .. } => transform.as_ref(),
+            ObjectDescription::Quad { transform, .. } => transform.as_ref(),
+            ObjectDescription::Disk { transform, .. } => transform.as_ref(),
+            ObjectDescription::Box { transform, .. } => transform.as_ref(),
+            ObjectDescription::Mesh { transform, .. } => transform.as_ref(),
+        }
+    }
+
+    pub fn motion(&self) -> Option<&TransformDescription> {
+        match self {
+            ObjectDescription::Sphere { motion, .. } => motion.as_ref(),
+            ObjectDescription::Quad { motion, .. } => motion.as_ref(),
+            ObjectDescription::Disk { motion, .. } => motion.as_ref(),
+            ObjectDescription::Box { motion, .. } => motion.as_ref(),
+            ObjectDescription::Mesh { motion, .. } => motion.as_ref(),
+        }
+    }
+}
+
+/// Density grid file (see `DensityGrid::open`) stretched over the box between
+/// `min` and `max`. Its densities times `absorption` and `scattering` are the
+/// coefficients per unit of distance, `anisotropy` in (-1, 1) is the
+/// Henyey-Greenstein g, positive for scattering forward.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeDescription {
+    pub path: PathBuf,
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    #[serde(default)]
+    pub absorption: f64,
+    #[serde(default = "default_scattering")]
+    pub scattering: f64,
+    #[serde(default)]
+    pub anisotropy: f64,
+    #[serde(default)]
+    pub transform: Option<TransformDescription>,
+}
+
+fn default_scattering() -> f64 {
+    1.
+}
+
+impl VolumeDescription {
+    /// Why the parameters can't be rendered, if they can't
+    fn validate(&self) -> Option<&'static str> {
+        let coefficients = [self.absorption, self.scattering];
+        if coefficients.iter().any(|c| !c.is_finite() || *c < 0.) {
+            Some("absorption and scattering have to be finite and can't be negative")
+        } else if self.anisotropy.is_nan() || self.anisotropy.abs() >= 1. {
+            Some("anisotropy has to be in (-1, 1)")
+        } else if (0..3).any(|axis| self.min[axis] >= self.max[axis]) {
+            Some("min has to be below max on every axis")
+        } else if self.transform.as_ref().is_some_and(|t| t.build().is_none()) {
+            Some("the transform has a zero scale")
+        } else {
+            None
+        }
+    }
+}
+
+/// Scale, then rotations in degrees about the x, y and z axes, then translation
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformDescription {
+    #[serde(default = "default_scale")]
+    pub scale: ScaleDescription,
+    #[serde(default)]
+    pub rotate: [f64; 3],
+    #[serde(default)]
+    pub translate: [f64; 3],
+}
+
+/// Either one factor for all axes or one per axis
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum ScaleDescription {
+    Uniform(f64),
+    Axes([f64; 3]),
+}
+
+fn default_scale() -> ScaleDescription {
+    ScaleDescription::Uniform(1.)
+}
+
+impl TransformDescription {
+    /// `None` for a zero scale, which would flatten the object
+    pub fn build(&self) -> Option<Transform> {
+        let scale: Vec3 = match self.scale {
+            ScaleDescription::Uniform(factor) => Vec3::new(factor, factor, factor),
+            ScaleDescription::Axes(factors) => factors.into(),
+        };
+        if scale.x == 0. || scale.y == 0. || scale.z == 0. {
+            return None;
+        }
+        let [x, y, z] = self.rotate;
+        Some(
+            Transform::scale(scale)
+                .then(&Transform::rotate(Vec3::new(1., 0., 0.), x))
+                .then(&Transform::rotate(Vec3::new(0., 1., 0.), y))
+                .then(&Transform::rotate(Vec3::new(0., 0., 1.), z))
+                .then(&Transform::translate(self.translate.into())),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Syntax or schema error, line and column are 1-based when known
+    Parse {
+        path: String,
+        message: String,
+        position: Option<(usize, usize)>,
+    },
+    UnknownMaterial {
+        object: usize,
+        material: String,
+    },
+    /// A transform with a zero scale
+    InvalidTransform(usize),
+    /// An isotropic material with a density that isn't positive
+    InvalidDensity(String),
+    /// A rough conductor with a negative roughness
+    InvalidRoughness(String),
+    /// A rough conductor's tangent is zero or not finite
+    InvalidTangent(String),
+    InvalidVolume {
+        volume: usize,
+        message: &'static str,
+    },
+    UnknownTexture(String),
+    /// A texture that directly or indirectly contains itself
+    CyclicTexture(String),
+    InvalidBackground(String),
+    Texture(TextureError),
+    Mesh(ObjError),
+    Grid(GridError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "Could not read scene file {}: {}", path, source)
+            }
+            SceneError::Parse {
+                path,
+                message,
+                position: Some((line, column)),
+            } => write!(f, "{}:{}:{}: {}", path, line, column, message),
+            SceneError::Parse {
+                path,
+                message,
+                position: None,
+            } => write!(f, "{}: {}", path, message),
+            SceneError::UnknownMaterial { object, material } => write!(
+                f,
+                "Object #{} references unknown material `{}`",
+                object, material
+            ),
+            SceneError::InvalidTransform(object) => {
+                write!(f, "Object #{} has a transform with a zero scale", object)
+            }
+            SceneError::InvalidDensity(name) => {
+                write!(f, "Material `{}` needs a positive density", name)
+            }
+            SceneError::InvalidRoughness(name) => {
+                write!(f, "Material `{}` can't have a negative roughness", name)
+            }
+            SceneError::InvalidTangent(name) => {
+                write!(f, "Material `{}` needs a non-zero tangent", name)
+            }
+            SceneError::InvalidVolume { volume, message } => {
+                write!(f, "Volume #{}: {}", volume, message)
+            }
+            SceneError::UnknownTexture(name) => write!(f, "Unknown texture `{}`", name),
+            SceneError::CyclicTexture(name) => {
+                write!(f, "Texture `{}` references itself", name)
+            }
+            SceneError::InvalidBackground(message) => write!(f, "{}", message),
+            SceneError::Texture(err) => write!(f, "{}", err),
+            SceneError::Mesh(err) => write!(f, "{}", err),
+            SceneError::Grid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl SceneDescription {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneDescription, SceneError> {
+        let path_str = path.as_ref().display().to_string();
+        let contents = fs::read_to_string(&path).map_err(|source| SceneError::Io {
+            path: path_str.clone(),
+            source,
+        })?;
+        let base_dir = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        SceneDescription::parse(&contents, base_dir).map_err(|err| match err {
+            SceneError::Parse {
+                message, position, ..
+            } => SceneError::Parse {
+                path: path_str,
+                message,
+                position,
+            },
+            err => err,
+        })
+    }
+
+    /// Parses a scene, `base_dir` is where relative texture paths are looked up
+    pub fn parse(contents: &str, base_dir: &Path) -> Result<SceneDescription, SceneError> {
+        let mut description: SceneDescription = toml::from_str(contents).map_err(|err| {
+            let message = err.to_string();
+            // The position is reported separately, drop toml's own suffix
+            let message = match message.rfind(" at line ") {
+                Some(idx) => message[..idx].to_string(),
+                None => message,
+            };
+            SceneError::Parse {
+                path: "<scene>".into(),
+                position: err.line_col().map(|(line, col)| (line + 1, col + 1)),
+                message,
+            }
+        })?;
+
+        for (name, texture) in &description.textures {
+            if let TextureDescription::Image { path, filter, wrap } = texture {
+                let filter = match filter {
+                    FilterDescription::Nearest => TextureFilter::Nearest,
+                    FilterDescription::Bilinear => TextureFilter::Bilinear,
+                };
+                let wrap = match wrap {
+                    WrapDescription::Clamp => TextureWrap::Clamp,
+                    WrapDescription::Repeat => TextureWrap::Repeat,
+                };
+                let image = ImageTexture::open(base_dir.join(path), filter, wrap)
+                    .map_err(SceneError::Texture)?;
+                description.images.insert(name.clone(), Arc::new(image));
+            }
+        }
+
+        // Validate references up front so a bad file fails before any tracing
+        if let Some(background) = &description.background {
+            background.build().map_err(SceneError::InvalidBackground)?;
+        }
+        let materials = description.build_materials(DiffuseMethod::Lambert, &mut Pcg32::new(0))?;
+        for (name, material) in &description.materials {
+            match material {
+                MaterialDescription::Isotropic { density, .. }
+                    if density.is_nan() || *density <= 0. =>
+                {
+                    return Err(SceneError::InvalidDensity(name.clone()));
+                }
+                MaterialDescription::RoughConductor { roughness, .. }
+                    if roughness.values().iter().any(|r| r.is_nan() || *r < 0.) =>
+                {
+                    return Err(SceneError::InvalidRoughness(name.clone()));
+                }
+                MaterialDescription::RoughConductor { tangent, .. }
+                    if tangent.iter().any(|c| !c.is_finite()) || *tangent == [0.; 3] =>
+                {
+                    return Err(SceneError::InvalidTangent(name.clone()));
+                }
+                _ => {}
+            }
+        }
+        for (idx, object) in description.objects.iter().enumerate() {
+            let material = object.material();
+            if !description.materials.contains_key(material) {
+                return Err(SceneError::UnknownMaterial {
+                    object: idx,
+                    material: material.into(),
+                });
+            }
+            for transform in object.transform().into_iter().chain(object.motion()) {
+                transform.build().ok_or(SceneError::InvalidTransform(idx))?;
+            }
+        }
+
+        // Every file is only read once, however many objects use it
+        let mut loaded: HashMap<&PathBuf, Arc<TriangleMesh>> = HashMap::new();
+        let mut meshes = HashMap::new();
+        for (idx, object) in description.objects.iter().enumerate() {
+            if let ObjectDescription::Mesh { path, material, .. } = object {
+                let mesh = match loaded.get(path) {
+                    Some(mesh) => mesh.clone(),
+                    None => {
+                        let material = materials[material.as_str()].clone();
+                        let mesh = load_obj(base_dir.join(path), material)
+                            .map_err(SceneError::Mesh)?;
+                        loaded.entry(path).or_insert(Arc::new(mesh)).clone()
+                    }
+                };
+                meshes.insert(idx, mesh);
+            }
+        }
+        description.meshes = meshes;
+
+        let mut grids = HashMap::new();
+        for (idx, volume) in description.volumes.iter().enumerate() {
+            if let Some(message) = volume.validate() {
+                return Err(SceneError::InvalidVolume {
+                    volume: idx,
+                    message,
+                });
+            }
+            if !grids.contains_key(&volume.path) {
+                let grid =
+                    DensityGrid::open(base_dir.join(&volume.path)).map_err(SceneError::Grid)?;
+                grids.insert(volume.path.clone(), Arc::new(grid));
+            }
+        }
+        description.grids = grids;
+
+        Ok(description)
+    }
+
+    fn build_texture(
+        &self,
+        reference: &TextureRef,
+        textures: &mut HashMap<String, Arc<dyn Texture>>,
+        visiting: &mut Vec<String>,
+        rng: &mut Pcg32,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let name = match reference {
+            TextureRef::Color(color) => return Ok(Arc::new(SolidColor::new((*color).into()))),
+            TextureRef::Named(name) => name,
+        };
+        if let Some(texture) = textures.get(name) {
+            return Ok(texture.clone());
+        }
+        if visiting.contains(name) {
+            return Err(SceneError::CyclicTexture(name.clone()));
+        }
+        let description = self
+            .textures
+            .get(name)
+            .ok_or_else(|| SceneError::UnknownTexture(name.clone()))?;
+
+        visiting.push(name.clone());
+        let texture: Arc<dyn Texture> = match description {
+            TextureDescription::Solid { color } => Arc::new(SolidColor::new((*color).into())),
+            TextureDescription::Checker { odd, even, scale } => Arc::new(Checker::new(
+                self.build_texture(odd, textures, visiting, rng)?,
+                self.build_texture(even, textures, visiting, rng)?,
+                *scale,
+            )),
+            TextureDescription::Noise { kind, scale } => {
+                let kind = match kind {
+                    NoiseKindDescription::Noise => NoiseKind::Noise,
+                    NoiseKindDescription::Turbulence => NoiseKind::Turbulence,
+                    NoiseKindDescription::Marble => NoiseKind::Marble,
+                };
+                Arc::new(NoiseTexture::new(kind, *scale, rng))
+            }
+            TextureDescription::Image { .. } => self.images[name].clone(),
+        };
+        visiting.pop();
+
+        textures.insert(name.clone(), texture.clone());
+        Ok(texture)
+    }
+
+    fn build_materials(
+        &self,
+        diffuse_method: DiffuseMethod,
+        rng: &mut Pcg32,
+    ) -> Result<HashMap<&str, Arc<dyn Material>>, SceneError> {
+        // Shared between materials so e.g. a noise texture is only generated once
+        let mut textures = HashMap::new();
+        let mut texture = |reference: &TextureRef| {
+            self.build_texture(reference, &mut textures, &mut Vec::new(), rng)
+        };
+
+        // In a fixed order so noise textures draw the same numbers on every build
+        let mut names: Vec<&String> = self.materials.keys().collect();
+        names.sort();
+
+        let mut materials = HashMap::new();
+        for name in names {
+            let material = &self.materials[name];
+            let material: Arc<dyn Material> = match material {
+                MaterialDescription::Lambertian { albedo } => {
+                    Arc::new(Lambertian::new(texture(albedo)?, diffuse_method))
+                }
+                MaterialDescription::Metal { albedo, fuzz } => {
+                    Arc::new(Metal::new(texture(albedo)?, *fuzz))
+                }
+                MaterialDescription::Dielectric { ir } => Arc::new(Dielectric::new(*ir)),
+                MaterialDescription::DiffuseLight { emit } => {
+                    Arc::new(DiffuseLight::new(texture(emit)?))
+                }
+                MaterialDescription::RoughConductor {
+                    conductor,
+                    roughness,
+                    tangent,
+                } => {
+                    let (eta, k) = match conductor {
+                        ConductorDescription::Preset(preset) => {
+                            let conductor = match preset {
+                                ConductorPreset::Gold => Conductor::Gold,
+                                ConductorPreset::Copper => Conductor::Copper,
+                                ConductorPreset::Aluminium => Conductor::Aluminium,
+                                ConductorPreset::Silver => Conductor::Silver,
+                            };
+                            conductor.ior()
+                        }
+                        ConductorDescription::Ior { eta, k } => ((*eta).into(), (*k).into()),
+                    };
+                    let [u, v] = roughness.values();
+                    Arc::new(RoughConductor::anisotropic(eta, k, (u, v), (*tangent).into()))
+                }
+                MaterialDescription::Isotropic { albedo, .. } => {
+                    Arc::new(Isotropic::new(texture(albedo)?))
+                }
+            };
+            materials.insert(name.as_str(), material);
+        }
+        Ok(materials)
+    }
+
+    pub fn build(&self, diffuse_method: DiffuseMethod, settings: &RenderSettings) -> Scene {
+        // Validated when parsing
+        let materials = self
+            .build_materials(diffuse_method, &mut Pcg32::new(settings.seed))
+            .unwrap();
+
+        let mut world: HittableList<dyn Hittable> = HittableList::new();
+        let mut media: HittableList<dyn Hittable> = HittableList::new();
+        let mut lights: HittableList<dyn Hittable> = HittableList::new();
+        // Mesh hierarchies by file and material, shared by the objects using them
+        let mut mesh_bvhs: HashMap<(&PathBuf, &str), Arc<dyn Hittable>> = HashMap::new();
+        for (idx, object) in self.objects.iter().enumerate() {
+            let material = materials[object.material()].clone();
+            let emissive = matches!(
+                self.materials[object.material()],
+                MaterialDescription::DiffuseLight { .. }
+            );
+            let density = match self.materials[object.material()] {
+                MaterialDescription::Isotropic { density, .. } => Some(density),
+                _ => None,
+            };
+            let hittable: Arc<dyn Hittable> = match object {
+                ObjectDescription::Sphere { center, radius, .. } => {
+                    Arc::new(Sphere::new((*center).into(), *radius, material))
+                }
+                ObjectDescription::Quad { q, u, v, .. } => {
+                    Arc::new(Quad::new((*q).into(), (*u).into(), (*v).into(), material))
+                }
+                ObjectDescription::Disk {
+                    center,
+                    normal,
+                    radius,
+                    ..
+                } => Arc::new(Disk::new((*center).into(), (*normal).into(), *radius, material)),
+                ObjectDescription::Box { min, max, .. } => {
+                    Arc::new(Cuboid::new((*min).into(), (*max).into(), material))
+                }
+                ObjectDescription::Mesh { path, .. } => mesh_bvhs
+                    .entry((path, object.material()))
+                    .or_insert_with(|| {
+                        // Rebound as the material depends on the diffuse method
+                        let mesh = TriangleMesh {
+                            material,
+                            ..self.meshes[&idx].as_ref().clone()
+                        };
+                        Arc::new(mesh.into_bvh())
+                    })
+                    .clone(),
+            };
+            let transform = object.transform().map(|transform| transform.build().unwrap());
+            let hittable: Arc<dyn Hittable> = match (transform, object.motion()) {
+                (transform, Some(motion)) => {
+                    let [open, close] = self.camera.shutter;
+                    Arc::new(Instance::moving(
+                        hittable,
+                        transform.unwrap_or_default(),
+                        motion.build().unwrap(),
+                        open,
+                        close,
+                    ))
+                }
+                (Some(transform), None) => Arc::new(Instance::new(hittable, transform)),
+                (None, None) => hittable,
+            };
+            // Filled once placed, so the density is per unit of world space
+            let hittable: Arc<dyn Hittable> = match density {
+                Some(density) => Arc::new(ConstantMedium::with_phase_function(
+                    hittable,
+                    density,
+                    materials[object.material()].clone(),
+                )),
+                None => hittable,
+            };
+            // Emissive spheres, quads and disks are area lights, boxes, meshes and
+            // moving objects are only found by scattering
+            let samplable = object.motion().is_none()
+                && matches!(
+                    object,
+                    ObjectDescription::Sphere { .. }
+                        | ObjectDescription::Quad { .. }
+                        | ObjectDescription::Disk { .. }
+                );
+            if emissive && samplable {
+                lights.add(hittable.clone());
+            }
+            match density {
+                Some(_) => media.add(hittable),
+                None => world.add(hittable),
+            }
+        }
+
+        for volume in &self.volumes {
+            let medium = Arc::new(GridMedium::new(
+                self.grids[&volume.path].clone(),
+                Aabb::new(volume.min.into(), volume.max.into()),
+                volume.absorption,
+                volume.scattering,
+                volume.anisotropy,
+            ));
+            match &volume.transform {
+                Some(transform) => {
+                    media.add(Arc::new(Instance::new(medium, transform.build().unwrap())))
+                }
+                None => media.add(medium),
+            }
+        }
+
+        Scene {
+            camera: self.camera.build(settings),
+            world,
+            media,
+            lights,
+            background: self
+                .background
+                .as_ref()
+                .map_or_else(Background::default, |background| background.build().unwrap()),
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn build(&self, settings: &RenderSettings) -> Camera {
+        let lookfrom: Point3 = self.lookfrom.into();
+        let lookat: Point3 = self.lookat.into();
+        let vup: Vec3 = self.vup.into();
+        let focus_dist = self
+            .focus_dist
+            .unwrap_or_else(|| (lookfrom - lookat).length());
+        let [open, close] = self.shutter;
+        Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            self.vfov,
+            settings.aspect_ratio(),
+            self.aperture,
+            focus_dist,
+        )
+        .with_shutter(open, close)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\n\
+                          vfov = 40.0\n";
+
+    fn parse(rest: &str) -> Result<SceneDescription, SceneError> {
+        // Top-level keys like `background` have to come before any table
+        SceneDescription::parse(&format!("{}\n{}", rest, CAMERA), Path::new(""))
+    }
+
+    #[test]
+    fn loads_the_bundled_scenes() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let settings = RenderSettings::new(16, 9, 1, 4);
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let description = SceneDescription::load(&path)
+                .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+            let scene = description.build(DiffuseMethod::Lambert, &settings);
+            assert!(!scene.world.objects.is_empty(), "{} is empty", path.display());
+        }
+    }
+
+    #[test]
+    fn parses_objects_materials_and_textures() {
+        let description = parse(
+            r#"
+            background = "black"
+
+            [textures.checker]
+            type = "checker"
+            odd = [0.0, 0.0, 0.0]
+            even = "white"
+
+            [textures.white]
+            type = "solid"
+            color = [1.0, 1.0, 1.0]
+
+            [materials.ground]
+            type = "lambertian"
+            albedo = "checker"
+
+            [materials.mirror]
+            type = "metal"
+            albedo = [0.8, 0.8, 0.8]
+            fuzz = 0.1
+
+            [materials.glass]
+            type = "dielectric"
+            ir = 1.5
+
+            [materials.lamp]
+            type = "diffuse_light"
+            emit = [4.0, 4.0, 4.0]
+
+            [[objects]]
+            type = "sphere"
+            center = [0.0, -100.0, 0.0]
+            radius = 100.0
+            material = "ground"
+            transform = { scale = 2.0, rotate = [0.0, 45.0, 0.0] }
+
+            [[objects]]
+            type = "sphere"
+            center = [1.0, 1.0, 0.0]
+            radius = 1.0
+            material = "mirror"
+
+            [[objects]]
+            type = "sphere"
+            center = [-1.0, 1.0, 0.0]
+            radius = 1.0
+            material = "glass"
+
+            [[objects]]
+            type = "quad"
+            q = [0.0, 3.0, 0.0]
+            u = [1.0, 0.0, 0.0]
+            v = [0.0, 0.0, 1.0]
+            material = "lamp"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(description.textures.len(), 2);
+        assert_eq!(description.materials.len(), 4);
+        let scene = description.build(DiffuseMethod::Lambert, &RenderSettings::new(16, 9, 1, 4));
+        assert_eq!(scene.world.objects.len(), 4);
+        // The lamp is sampled directly
+        assert_eq!(scene.lights.objects.len(), 1);
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_their_position() {
+        match parse("\n[materials.broken\n") {
+            Err(SceneError::Parse {
+                position: Some((line, _)),
+                ..
+            }) => assert_eq!(line, 2),
+            result => panic!("{:?}", result.map(|_| ())),
+        }
+        assert!(matches!(parse("colour = 1\n"), Err(SceneError::Parse { .. })));
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        let object = "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+                      material = \"missing\"\n";
+        assert!(matches!(parse(object), Err(SceneError::UnknownMaterial { object: 0, .. })));
+
+        let material = "[materials.m]\ntype = \"lambertian\"\nalbedo = \"missing\"\n";
+        assert!(matches!(parse(material), Err(SceneError::UnknownTexture(_))));
+    }
+
+    #[test]
+    fn rejects_cyclic_textures() {
+        let textures = "[textures.a]\ntype = \"checker\"\nodd = \"b\"\neven = [0.0, 0.0, 0.0]\n\
+                        [textures.b]\ntype = \"checker\"\nodd = \"a\"\neven = [0.0, 0.0, 0.0]\n\
+                        [materials.m]\ntype = \"lambertian\"\nalbedo = \"a\"\n";
+        assert!(matches!(parse(textures), Err(SceneError::CyclicTexture(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        let sphere = |material: &str, extra: &str| {
+            format!(
+                "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+                 material = \"{}\"\n{}\n",
+                material, extra
+            )
+        };
+        let white = "[materials.white]\ntype = \"lambertian\"\nalbedo = [1.0, 1.0, 1.0]\n";
+        let zero_scale = format!("{}{}", white, sphere("white", "transform = { scale = 0.0 }"));
+        assert!(matches!(parse(&zero_scale), Err(SceneError::InvalidTransform(0))));
+
+        let fog = "[materials.fog]\ntype = \"isotropic\"\nalbedo = [1.0, 1.0, 1.0]\n\
+                   density = 0.0\n";
+        assert!(matches!(parse(fog), Err(SceneError::InvalidDensity(_))));
+
+        let rough = "[materials.m]\ntype = \"rough_conductor\"\nconductor = \"gold\"\n\
+                     roughness = [0.1, -0.1]\n";
+        assert!(matches!(parse(rough), Err(SceneError::InvalidRoughness(_))));
+
+        let tangent = "[materials.m]\ntype = \"rough_conductor\"\nconductor = \"gold\"\n\
+                       tangent = [0.0, 0.0, 0.0]\n";
+        assert!(matches!(parse(tangent), Err(SceneError::InvalidTangent(_))));
+
+        let background = "background = \"sunset\"\n";
+        assert!(matches!(parse(background), Err(SceneError::InvalidBackground(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_volumes() {
+        let volume = |extra: &str| {
+            format!(
+                "[[volumes]]\npath = \"smoke.vol\"\nmin = [0.0, 0.0, 0.0]\n\
+                 max = [1.0, 1.0, 1.0]\n{}\n",
+                extra
+            )
+        };
+        for extra in ["absorption = -1.0", "scattering = inf", "anisotropy = 1.0"] {
+            assert!(
+                matches!(parse(&volume(extra)), Err(SceneError::InvalidVolume { volume: 0, .. })),
+                "{}",
+                extra
+            );
+        }
+        assert!(matches!(parse(&volume("")), Err(SceneError::Grid(GridError::Io { .. }))));
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let mesh = "[materials.white]\ntype = \"lambertian\"\nalbedo = [1.0, 1.0, 1.0]\n\
+                    [[objects]]\ntype = \"mesh\"\npath = \"missing.obj\"\nmaterial = \"white\"\n";
+        assert!(matches!(parse(mesh), Err(SceneError::Mesh(ObjError::Io { .. }))));
+    }
+}
@@ -0,0 +1,330 @@
+pub mod file;
+pub mod obj;
+
+use crate::core::{
+    background::Background,
+    camera::Camera,
+    color::Color,
+    hit::Hittable,
+    hittable_list::HittableList,
+    instance::Instance,
+    material::{Material, Dielectric, DiffuseLight, DiffuseMethod, Lambertian, Metal},
+    medium::ConstantMedium,
+    quad::{Cuboid, Quad},
+    rng::Pcg32,
+    sphere::{MovingSphere, Sphere},
+    texture::SolidColor,
+    transform::Transform,
+    vec3::{Length, Point3, Vec3},
+};
+use crate::settings::RenderSettings;
+use rand::Rng;
+use std::sync::Arc;
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+/// A world ready to be traced, the camera looking at it and what lies beyond it
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList<dyn Hittable>,
+    /// Volumes, kept out of the world so shadow rays can see through them
+    pub media: HittableList<dyn Hittable>,
+    /// Emissive objects of the world that are sampled directly
+    pub lights: HittableList<dyn Hittable>,
+    pub background: Background,
+}
+
+/// Scenes built in code, rendered when no scene file is given
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab_case")]
+pub enum BuiltinScene {
+    /// The final scene of the first book, small random spheres around three big ones
+    Random,
+    /// Red and green walls, two white boxes and a ceiling light, best square
+    CornellBox,
+    /// The first scene of the second book, the random spheres with the small
+    /// diffuse ones bouncing while the shutter is open
+    BouncingSpheres,
+    /// The Cornell box with boxes of black and white smoke, best square
+    CornellSmoke,
+}
+
+impl BuiltinScene {
+    pub fn build(self, diffuse_method: &mut DiffuseMethod, settings: &RenderSettings) -> Scene {
+        match self {
+            BuiltinScene::Random => {
+                let rng = &mut Pcg32::new(settings.seed);
+                Scene {
+                    camera: get_camera(settings),
+                    world: random_spheres(*diffuse_method, rng, false),
+                    media: HittableList::new(),
+                    lights: HittableList::new(),
+                    background: Background::Gradient,
+                }
+            }
+            BuiltinScene::CornellBox => cornell_box(*diffuse_method, settings),
+            BuiltinScene::BouncingSpheres => bouncing_spheres(*diffuse_method, settings),
+            BuiltinScene::CornellSmoke => cornell_smoke(*diffuse_method, settings),
+        }
+    }
+}
+
+pub fn get_camera(settings: &RenderSettings) -> Camera {
+    let lookfrom = Point3::new(13., 2., 3.);
+    let lookat = Point3::new(0., 0., 0.);
+    // let lookfrom = Point3::new(3., 3., 2.);
+    // let lookat = Point3::new(0., 0., -1.);
+    let vup = Point3::new(0., 1., 0.);
+    let dist_to_focus = 10.; // (lookfrom - lookat).length();
+    let aperture = 0.1;// 2.0;
+    Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        20.,
+        settings.aspect_ratio(),
+        aperture,
+        dist_to_focus,
+    )
+}
+
+pub fn generate_scene(diffuse_method: &mut DiffuseMethod) -> HittableList<impl Hittable> {
+    // Materials
+    let material_ground = Arc::new(Lambertian::new(
+        Arc::new(SolidColor::new(Color::new_rgb(204, 204, 0))),
+        *diffuse_method,
+    ));
+    let material_center = Arc::new(Lambertian::new(
+        Arc::new(SolidColor::new(Color::new(0.1, 0.2, 0.5))),
+        *diffuse_method,
+    ));
+    let material_left = Arc::new(Dielectric::new(1.5));
+    let material_right = Arc::new(Metal::new(
+        Arc::new(SolidColor::new(Color::new(0.8, 0.6, 0.2))),
+        0.,
+    ));
+
+    // Objects
+    let planet = Arc::new(Sphere::new(
+        Point3::new(0., -100.5, -1.),
+        100.,
+        material_ground,
+    ));
+    let sphere_center = Arc::new(Sphere::new(Point3::new(0., 0., -1.), 0.5, material_center));
+    let sphere_left = Arc::new(Sphere::new(
+        Point3::new(-1., 0., -1.),
+        0.5,
+        material_left.clone(),
+    ));
+    let sphere_left_2 = Arc::new(Sphere::new(Point3::new(-1., 0., -1.), -0.45, material_left));
+    let sphere_right = Arc::new(Sphere::new(Point3::new(1., 0., -1.), 0.5, material_right));
+
+    // World
+    let mut world = HittableList::new();
+    world.add(planet);
+    world.add(sphere_center);
+    world.add(sphere_left);
+    world.add(sphere_left_2);
+    world.add(sphere_right);
+
+    world
+}
+
+pub fn random_scene(
+    diffuse_method: &mut DiffuseMethod,
+    rng: &mut Pcg32,
+) -> HittableList<dyn Hittable> {
+    random_spheres(*diffuse_method, rng, false)
+}
+
+/// The random scene with the small diffuse spheres moving up by up to half a unit
+/// between the shutter opening at 0 and closing at 1
+pub fn bouncing_spheres(diffuse_method: DiffuseMethod, settings: &RenderSettings) -> Scene {
+    Scene {
+        camera: get_camera(settings).with_shutter(0., 1.),
+        world: random_spheres(diffuse_method, &mut Pcg32::new(settings.seed), true),
+        media: HittableList::new(),
+        lights: HittableList::new(),
+        background: Background::Gradient,
+    }
+}
+
+/// Small random spheres around three big ones, with `bouncing` the small diffuse
+/// ones move up from time 0 to 1
+fn random_spheres(
+    diffuse_method: DiffuseMethod,
+    rng: &mut Pcg32,
+    bouncing: bool,
+) -> HittableList<dyn Hittable> {
+    let mut world: HittableList<dyn Hittable> = HittableList::new();
+
+    let ground_material = Arc::new(Lambertian::new(
+        Arc::new(SolidColor::new(Color::new(0.5, 0.5, 0.5))),
+        diffuse_method,
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        ground_material,
+    )));
+
+    // Small spheres
+    for au in -11..11 {
+        let a = au as f64;
+        for bu in -11..11 {
+            let b = bu as f64;
+            let material_choice = rng.gen::<f64>();
+            let center = Point3::new(a + 0.9 * rng.gen::<f64>(), 0.2, b + 0.9 * rng.gen::<f64>());
+
+            if (center - Point3::new(4., 0.2, 0.)).length() > 0.9 {
+                if material_choice < 0.8 {
+                    // Diffuse
+                    let albedo = Color::random(rng) * Color::random(rng);
+                    let sphere_material = Arc::new(Lambertian::new(
+                        Arc::new(SolidColor::new(albedo)),
+                        diffuse_method,
+                    ));
+                    if bouncing {
+                        let bounce = Vec3::new(0., rng.gen_range(0.0..0.5), 0.);
+                        world.add(Arc::new(MovingSphere::new(
+                            center,
+                            center + bounce,
+                            0.,
+                            1.,
+                            0.2,
+                            sphere_material,
+                        )));
+                    } else {
+                        world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                    }
+                    continue;
+                }
+                let sphere_material: Arc<dyn Material> = if material_choice < 0.95 {
+                    // Metal
+                    let albedo = Color::random_limit(0.5, 1., rng);
+                    let fuzz = rng.gen_range(0.0..0.5);
+                    Arc::new(Metal::new(Arc::new(SolidColor::new(albedo)), fuzz))
+                } else {
+                    // Glass
+                    Arc::new(Dielectric::new(1.5))
+                };
+                world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+            }
+        }
+    }
+
+    // Big spheres
+    let material1 = Arc::new(Dielectric::new(1.5));
+    let material2 = Arc::new(Lambertian::new(
+        Arc::new(SolidColor::new(Color::new(0.4, 0.2, 0.1))),
+        diffuse_method,
+    ));
+    let material3 = Arc::new(Metal::new(
+        Arc::new(SolidColor::new(Color::new(0.7, 0.6, 0.5))),
+        0.,
+    ));
+
+    world.add(Arc::new(Sphere::new(Point3::new(0., 1., 0.), 1., material1)));
+    world.add(Arc::new(Sphere::new(Point3::new(-4., 1., 0.), 1., material2)));
+    world.add(Arc::new(Sphere::new(Point3::new(4., 1., 0.), 1., material3)));
+
+    world
+}
+
+/// The Cornell box from the second book, 555 units on each side
+pub fn cornell_box(diffuse_method: DiffuseMethod, settings: &RenderSettings) -> Scene {
+    let lamp = Quad::new(
+        Point3::new(343., 554., 332.),
+        Vec3::new(-130., 0., 0.),
+        Vec3::new(0., 0., -105.),
+        Arc::new(DiffuseLight::new(Arc::new(SolidColor::new(Color::new(15., 15., 15.))))),
+    );
+    let mut scene = cornell_room(diffuse_method, lamp, settings);
+    let white = lambertian(Color::new(0.73, 0.73, 0.73), diffuse_method);
+    let [tall, short] = cornell_boxes(&white);
+    scene.world.add(tall);
+    scene.world.add(short);
+    scene
+}
+
+/// The Cornell box with its boxes turned into black and white smoke under a
+/// larger, dimmer lamp
+pub fn cornell_smoke(diffuse_method: DiffuseMethod, settings: &RenderSettings) -> Scene {
+    let lamp = Quad::new(
+        Point3::new(113., 554., 127.),
+        Vec3::new(330., 0., 0.),
+        Vec3::new(0., 0., 305.),
+        Arc::new(DiffuseLight::new(Arc::new(SolidColor::new(Color::new(7., 7., 7.))))),
+    );
+    let mut scene = cornell_room(diffuse_method, lamp, settings);
+    // Only the boundaries matter, their material is never seen
+    let white = lambertian(Color::new(0.73, 0.73, 0.73), diffuse_method);
+    let [tall, short] = cornell_boxes(&white);
+    let smoke = |boundary, color| {
+        Arc::new(ConstantMedium::new(boundary, 0.01, Arc::new(SolidColor::new(color))))
+    };
+    scene.media.add(smoke(tall, Color::new(0., 0., 0.)));
+    scene.media.add(smoke(short, Color::new(1., 1., 1.)));
+    scene
+}
+
+fn lambertian(color: Color, diffuse_method: DiffuseMethod) -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Arc::new(SolidColor::new(color)), diffuse_method))
+}
+
+/// Red and green walls, a white floor, ceiling and back, and the `lamp`
+fn cornell_room(diffuse_method: DiffuseMethod, lamp: Quad, settings: &RenderSettings) -> Scene {
+    let red = lambertian(Color::new(0.65, 0.05, 0.05), diffuse_method);
+    let white = lambertian(Color::new(0.73, 0.73, 0.73), diffuse_method);
+    let green = lambertian(Color::new(0.12, 0.45, 0.15), diffuse_method);
+
+    let mut world: HittableList<dyn Hittable> = HittableList::new();
+    let mut wall = |q: Point3, u: Vec3, v: Vec3, material: &Arc<dyn Material>| {
+        world.add(Arc::new(Quad::new(q, u, v, material.clone())))
+    };
+    let (x, y, z) = (Vec3::new(555., 0., 0.), Vec3::new(0., 555., 0.), Vec3::new(0., 0., 555.));
+    let origin = Point3::new(0., 0., 0.);
+    wall(x, y, z, &green);
+    wall(origin, y, z, &red);
+    wall(origin, x, z, &white);
+    wall(y, x, z, &white);
+    wall(z, x, y, &white);
+
+    // Slightly below the ceiling so the ceiling doesn't hide it
+    let lamp = Arc::new(lamp);
+    world.add(lamp.clone());
+    let mut lights: HittableList<dyn Hittable> = HittableList::new();
+    lights.add(lamp);
+
+    let lookfrom = Point3::new(278., 278., -800.);
+    let lookat = Point3::new(278., 278., 0.);
+    Scene {
+        camera: Camera::new(
+            lookfrom,
+            lookat,
+            Vec3::new(0., 1., 0.),
+            40.,
+            settings.aspect_ratio(),
+            0.,
+            (lookat - lookfrom).length(),
+        ),
+        world,
+        media: HittableList::new(),
+        lights,
+        background: Background::Black,
+    }
+}
+
+/// The tall and the short box, with a corner at the origin, turned about the y axis
+/// and moved in place
+fn cornell_boxes(material: &Arc<dyn Material>) -> [Arc<dyn Hittable>; 2] {
+    let turned_box = |size: Vec3, degrees: f64, offset: Vec3| -> Arc<dyn Hittable> {
+        let cuboid = Arc::new(Cuboid::new(Point3::new(0., 0., 0.), size, material.clone()));
+        let transform = Transform::rotate(Vec3::new(0., 1., 0.), degrees)
+            .then(&Transform::translate(offset));
+        Arc::new(Instance::new(cuboid, transform))
+    };
+    [
+        turned_box(Vec3::new(165., 330., 165.), 15., Vec3::new(265., 0., 295.)),
+        turned_box(Vec3::new(165., 165., 165.), -18., Vec3::new(130., 0., 65.)),
+    ]
+}
@@ -0,0 +1,254 @@
+use std::{fmt, fs, path::Path, str::FromStr, sync::Arc};
+
+use crate::core::{
+    material::Material,
+    triangle::{MeshVertex, TriangleMesh},
+    vec3::Vec3,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Malformed statement, the line is 1-based
+    Parse {
+        path: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "Could not read mesh {}: {}", path, source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// Loads a Wavefront OBJ file into a single mesh.
+///
+/// Only geometry is read (`v`, `vt`, `vn` and `f` statements), polygons are
+/// triangulated as fans and everything else (groups, materials, ...) is ignored.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    material: Arc<dyn Material>,
+) -> Result<TriangleMesh, ObjError> {
+    let path_str = path.as_ref().display().to_string();
+    let contents = fs::read_to_string(&path).map_err(|source| ObjError::Io {
+        path: path_str.clone(),
+        source,
+    })?;
+    parse_obj(&contents, material).map_err(|(line, message)| ObjError::Parse {
+        path: path_str,
+        line,
+        message,
+    })
+}
+
+/// Parses OBJ contents, errors carry the 1-based line number
+pub fn parse_obj(
+    contents: &str,
+    material: Arc<dyn Material>,
+) -> Result<TriangleMesh, (usize, String)> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut faces = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        let number = |arg: &str| -> Result<f64, (usize, String)> {
+            f64::from_str(arg)
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| (line_number, format!("Invalid number `{}`", arg)))
+        };
+        let floats = |count: usize| -> Result<Vec<f64>, (usize, String)> {
+            if args.len() < count {
+                return Err((
+                    line_number,
+                    format!("`{}` expects {} values, got {}", keyword, count, args.len()),
+                ));
+            }
+            args.iter().take(count).map(|arg| number(arg)).collect()
+        };
+
+        match keyword {
+            "v" => {
+                let v = floats(3)?;
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                // The optional third texture coordinate is ignored
+                let vt = floats(1)?;
+                let v = args.get(1).map_or(Ok(0.), |arg| number(arg))?;
+                uvs.push((vt[0], v));
+            }
+            "vn" => {
+                let n = floats(3)?;
+                let normal = Vec3::new(n[0], n[1], n[2]);
+                // Normalizing would turn it into NaNs
+                if normal.near_zero() {
+                    return Err((line_number, "Zero-length normal".into()));
+                }
+                normals.push(normal.normalize());
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err((line_number, "Face with less than 3 vertices".into()));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| {
+                        parse_face_vertex(arg, positions.len(), uvs.len(), normals.len())
+                            .map_err(|message| (line_number, message))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Fan triangulation, exact for the convex polygons OBJ exporters emit
+                for i in 1..corners.len() - 1 {
+                    faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // An empty mesh has no bounding box to place it by
+    if faces.is_empty() {
+        return Err((contents.lines().count().max(1), "Mesh without faces".into()));
+    }
+    Ok(TriangleMesh::new(positions, uvs, normals, faces, material))
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, indices are 1-based or negative (relative)
+fn parse_face_vertex(
+    vertex: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<MeshVertex, String> {
+    let resolve = |index: &str, count: usize, kind: &str| -> Result<usize, String> {
+        let index = i64::from_str(index)
+            .map_err(|_| format!("Invalid {} index `{}` in `{}`", kind, index, vertex))?;
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range", kind, index));
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut parts = vertex.split('/');
+    let position = resolve(parts.next().unwrap_or(""), position_count, "Position")?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(resolve(uv, uv_count, "Texture")?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(resolve(normal, normal_count, "Normal")?),
+        _ => None,
+    };
+    Ok(MeshVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::Dielectric;
+
+    fn parse(contents: &str) -> Result<TriangleMesh, (usize, String)> {
+        parse_obj(contents, Arc::new(Dielectric::new(1.5)))
+    }
+
+    #[test]
+    fn parses_vertices_and_faces() {
+        let mesh = parse(
+            "# A quad\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0 0\nvt 1 1\nvt 0 1\n\
+             vn 0 0 2\n\
+             g quad\n\
+             f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+             f -4 -3 -2\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.uvs[1], (1., 0.));
+        assert_eq!(mesh.normals, vec![Vec3::new(0., 0., 1.)]);
+        // The quad is fanned into two triangles
+        assert_eq!(mesh.faces.len(), 3);
+        let [first, _, third] = mesh.faces[1];
+        assert_eq!((first.position, first.uv, first.normal), (0, Some(0), Some(0)));
+        assert_eq!((third.position, third.uv), (3, Some(3)));
+        let [first, second, _] = mesh.faces[2];
+        assert_eq!((first.position, second.position, first.uv), (0, 1, None));
+    }
+
+    #[test]
+    fn parses_face_vertex_forms() {
+        let vertex = parse_face_vertex("2//1", 3, 0, 1).unwrap();
+        assert_eq!((vertex.position, vertex.uv, vertex.normal), (1, None, Some(0)));
+        let vertex = parse_face_vertex("-1/2", 3, 2, 0).unwrap();
+        assert_eq!((vertex.position, vertex.uv, vertex.normal), (2, Some(1), None));
+    }
+
+    #[test]
+    fn rejects_indices_out_of_range() {
+        assert!(parse_face_vertex("0", 3, 0, 0).is_err());
+        assert!(parse_face_vertex("4", 3, 0, 0).is_err());
+        assert!(parse_face_vertex("-4", 3, 0, 0).is_err());
+        assert!(parse_face_vertex("1/1", 3, 0, 0).is_err());
+        assert!(parse_face_vertex("one", 3, 0, 0).is_err());
+    }
+
+    #[test]
+    fn reports_line_of_malformed_statements() {
+        let (line, _) = parse("v 0 0 0\nv 1 0\n").err().unwrap();
+        assert_eq!(line, 2);
+        let (line, _) = parse("v 0 0 0\nv 1 0 0\nf 1 2\n").err().unwrap();
+        assert_eq!(line, 3);
+        let (line, _) = parse("v 0 0 0\n\nv nan 0 0\n").err().unwrap();
+        assert_eq!(line, 3);
+        let (line, _) = parse("vt 0 0\nvt 0.5 inf\n").err().unwrap();
+        assert_eq!(line, 2);
+    }
+
+    #[test]
+    fn rejects_zero_length_normals() {
+        let (line, message) = parse("v 0 0 0\nvn 0 0 1\nvn 0 0 0\n").err().unwrap();
+        assert_eq!((line, message.as_str()), (3, "Zero-length normal"));
+    }
+
+    #[test]
+    fn rejects_meshes_without_faces() {
+        assert!(parse("").is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\n").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::core::sampler::SamplerKind;
+use crate::render::{film::Filter, tile::TileOrder};
+
+/// Image and sampling parameters of a render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    /// Side of the square tiles handed to the thread pool
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    /// Seeds every random number of a render, the same seed gives the same image
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    /// Pixel reconstruction filter the samples are splatted with
+    pub filter: Filter,
+    /// Adaptive sampling: after `samples_per_pixel` samples a pixel keeps being
+    /// sampled until the relative error of its mean drops below the threshold
+    pub noise_threshold: Option<f64>,
+    /// Cap on the samples of a pixel in adaptive sampling
+    pub max_samples_per_pixel: usize,
+}
+
+pub const DEFAULT_ASPECT_RATIO: f64 = 3. / 2.;
+pub const DEFAULT_TILE_SIZE: usize = 32;
+/// Default adaptive sampling cap as a multiple of the samples per pixel
+pub const DEFAULT_MAX_SAMPLES_FACTOR: usize = 8;
+
+impl RenderSettings {
+    pub fn new(
+        image_width: usize,
+        image_height: usize,
+        samples_per_pixel: usize,
+        max_depth: usize,
+    ) -> RenderSettings {
+        RenderSettings {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            tile_size: DEFAULT_TILE_SIZE,
+            tile_order: TileOrder::Spiral,
+            seed: 0,
+            sampler: SamplerKind::Independent,
+            filter: Filter::default(),
+            noise_threshold: None,
+            max_samples_per_pixel: DEFAULT_MAX_SAMPLES_FACTOR * samples_per_pixel,
+        }
+    }
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+    pub fn pixel_count(&self) -> usize {
+        self.image_width * self.image_height
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        let image_width = 600;
+        RenderSettings::new(
+            image_width,
+            (image_width as f64 / DEFAULT_ASPECT_RATIO) as usize,
+            10,
+            50,
+        )
+    }
+}
@@ -1,23 +1,119 @@
-use std::{fs::File, path::Path, io::prelude::*, time::Instant};
+use std::{fs::File, io::prelude::*, io::BufWriter, path::Path, time::Instant};
 
-use crate::core::color::Color;
-use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use exr::prelude::{f16, write_rgb_file};
+use image::{codecs::hdr::HdrEncoder, ColorType, ImageError, Rgb};
+use wort::core::color::Color;
+use wort::settings::RenderSettings;
 
-pub fn write_to_file(filename: String, pixels: &[Color]) -> std::io::Result<()> {
+use crate::sinks::{ExrPrecision, Sink};
+
+/// Writes the pixels, tone mapped for display formats and linear radiance for HDR formats
+pub fn write_to_file(
+    filename: String,
+    pixels: &[Color],
+    settings: &RenderSettings,
+    format: Sink,
+    exr_precision: ExrPrecision,
+) -> std::io::Result<()> {
     let path = Path::new("renders").join("staging");
     std::fs::create_dir_all(&path)?;
     eprintln!("Writing to {}", filename);
-    let mut file = File::create(path.join(filename))?;
+    let path = path.join(filename);
 
     let start = Instant::now();
+    match format {
+        Sink::File => write_ppm(&path, pixels, settings)?,
+        Sink::Png => write_png(&path, pixels, settings)?,
+        Sink::Pfm => write_pfm(&path, pixels, settings)?,
+        Sink::Hdr => write_hdr(&path, pixels, settings)?,
+        Sink::Exr => write_exr(&path, pixels, settings, exr_precision)?,
+        Sink::Window => unreachable!("The window isn't a file sink"),
+    }
+    eprintln!("Writing to file took {:.3}s", start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+fn write_ppm(path: &Path, pixels: &[Color], settings: &RenderSettings) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
     writeln!(file, "P3")?;
-    writeln!(file, "{} {}", IMAGE_WIDTH, IMAGE_HEIGHT)?;
+    writeln!(file, "{} {}", settings.image_width, settings.image_height)?;
     writeln!(file, "255")?;
     for color in pixels {
         let c_u8 = color.as_u8_slice();
         writeln!(file, "{} {} {}", c_u8[0], c_u8[1], c_u8[2],)?;
     }
-    eprintln!("Writing to file took {:.3}s", start.elapsed().as_secs_f64());
-    file.flush()?;
-    Ok(())
+    file.flush()
+}
+
+fn write_png(path: &Path, pixels: &[Color], settings: &RenderSettings) -> std::io::Result<()> {
+    let bytes: Vec<u8> = pixels.iter().flat_map(|color| color.as_u8_slice()).collect();
+    image::save_buffer(
+        path,
+        &bytes,
+        settings.image_width as u32,
+        settings.image_height as u32,
+        ColorType::Rgb8,
+    )
+    .map_err(image_to_io_error)
+}
+
+fn write_pfm(path: &Path, pixels: &[Color], settings: &RenderSettings) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    // Negative scale marks little-endian data
+    write!(
+        file,
+        "PF\n{} {}\n-1.0\n",
+        settings.image_width, settings.image_height
+    )?;
+    // Scanlines are stored bottom to top
+    for row in pixels.chunks(settings.image_width).rev() {
+        for color in row {
+            for channel in &[color.r, color.g, color.b] {
+                file.write_all(&(*channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+fn write_hdr(path: &Path, pixels: &[Color], settings: &RenderSettings) -> std::io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let rgb: Vec<Rgb<f32>> = pixels
+        .iter()
+        .map(|color| Rgb([color.r as f32, color.g as f32, color.b as f32]))
+        .collect();
+    HdrEncoder::new(file)
+        .encode(&rgb, settings.image_width, settings.image_height)
+        .map_err(image_to_io_error)
+}
+
+fn write_exr(
+    path: &Path,
+    pixels: &[Color],
+    settings: &RenderSettings,
+    precision: ExrPrecision,
+) -> std::io::Result<()> {
+    let pixel = |x: usize, y: usize| pixels[y * settings.image_width + x];
+    let (width, height) = (settings.image_width, settings.image_height);
+    match precision {
+        ExrPrecision::Half => write_rgb_file(path, width, height, |x, y| {
+            let c = pixel(x, y);
+            (f16::from_f64(c.r), f16::from_f64(c.g), f16::from_f64(c.b))
+        }),
+        ExrPrecision::Float => write_rgb_file(path, width, height, |x, y| {
+            let c = pixel(x, y);
+            (c.r as f32, c.g as f32, c.b as f32)
+        }),
+    }
+    .map_err(|err| match err {
+        exr::error::Error::Io(err) => err,
+        err => std::io::Error::other(err),
+    })
+}
+
+fn image_to_io_error(err: ImageError) -> std::io::Error {
+    match err {
+        ImageError::IoError(err) => err,
+        err => std::io::Error::other(err),
+    }
 }
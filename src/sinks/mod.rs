@@ -1,11 +1,62 @@
 pub mod file;
 pub mod window;
 
+use std::path::Path;
+
 use strum_macros::{EnumString, EnumVariantNames, IntoStaticStr};
 
 #[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr)]
 #[strum(serialize_all = "kebab_case")]
 pub enum Sink {
     Window,
+    /// ASCII PPM file
     File,
+    /// 8-bit PNG file
+    Png,
+    /// Portable float map, linear 32-bit float
+    Pfm,
+    /// Radiance RGBE, linear
+    Hdr,
+    /// OpenEXR, linear half or float
+    Exr,
+}
+
+/// Channel type of OpenEXR output
+#[derive(Debug, Copy, Clone, PartialEq, EnumString, EnumVariantNames, IntoStaticStr)]
+#[strum(serialize_all = "kebab_case")]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+impl Sink {
+    /// File extension written by the sink, `None` for the window
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Sink::Window => None,
+            Sink::File => Some("ppm"),
+            Sink::Png => Some("png"),
+            Sink::Pfm => Some("pfm"),
+            Sink::Hdr => Some("hdr"),
+            Sink::Exr => Some("exr"),
+        }
+    }
+
+    /// File sink matching the filename's extension
+    pub fn from_filename(filename: &str) -> Option<Sink> {
+        let extension = Path::new(filename).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Sink::File),
+            "png" => Some(Sink::Png),
+            "pfm" => Some(Sink::Pfm),
+            "hdr" => Some(Sink::Hdr),
+            "exr" => Some(Sink::Exr),
+            _ => None,
+        }
+    }
+
+    /// Whether the sink keeps the linear radiance rather than tone mapped values
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Sink::Pfm | Sink::Hdr | Sink::Exr)
+    }
 }
@@ -1,20 +1,41 @@
-use crate::core::{color::Color, material::DiffuseMethod};
-use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use wort::core::material::DiffuseMethod;
+use wort::render::{
+    film::{Film, FilmTile},
+    tile::tiles,
+    Renderer,
+};
+use wort::settings::RenderSettings;
+use wort::tonemap::{ToneMapOperator, ToneMapper};
 use enum_iterator::IntoEnumIterator;
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+/// A finished tile of one sample per pixel, tagged with the accumulation it was rendered for
+struct TileUpdate {
+    generation: usize,
+    film_tile: FilmTile,
+}
 
-pub fn draw_in_window<F>(mut trace: F, diffuse_method: &mut DiffuseMethod) -> std::io::Result<()>
+/// Renders progressively on a background thread, the window shows the running
+/// average as tiles finish and restarts it when the diffuse method changes
+pub fn draw_in_window<F>(
+    build: F,
+    mut diffuse_method: DiffuseMethod,
+    settings: &RenderSettings,
+    mut tone_mapper: ToneMapper,
+) -> std::io::Result<()>
 where
-    F: FnMut(&mut DiffuseMethod) -> std::io::Result<std::vec::Vec<Color>>,
+    F: Fn(DiffuseMethod) -> Renderer + Sync,
 {
-    let mut pixels = trace(diffuse_method).unwrap();
-
-    let mut buffer: Vec<u32> = vec![0; IMAGE_WIDTH * IMAGE_HEIGHT];
+    let mut buffer: Vec<u32> = vec![0; settings.pixel_count()];
 
     let mut window = Window::new(
         "ESC to exit",
-        IMAGE_WIDTH,
-        IMAGE_HEIGHT,
+        settings.image_width,
+        settings.image_height,
         WindowOptions {
             topmost: true,
             ..WindowOptions::default()
@@ -27,27 +48,153 @@ where
     // Limit to max ~60 fps update rate
     window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
-    let mut diffuse_method_iter = DiffuseMethod::into_enum_iter().cycle();
-    eprintln!("Using {} diffuse method.", diffuse_method.to_string());
+    // Continue cycling from the methods given on the command line
+    let initial_method = diffuse_method;
+    let mut diffuse_method_iter = DiffuseMethod::into_enum_iter()
+        .cycle()
+        .skip_while(move |method| *method != initial_method)
+        .skip(1);
+    eprintln!("Using {} diffuse method.", diffuse_method);
+
+    let initial_operator = tone_mapper.operator;
+    let mut operator_iter = ToneMapOperator::into_enum_iter()
+        .cycle()
+        .skip_while(move |operator| *operator != initial_operator)
+        .skip(1);
+    eprintln!("Using {} tone mapping.", tone_mapper.operator);
+
+    let (restart_sender, restart_receiver) = mpsc::channel();
+    let (tile_sender, tile_receiver) = mpsc::channel();
+    let tile_count = tiles(settings).len();
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        for (i, pixel) in buffer.iter_mut().zip(&pixels) {
-            let c_u8 = pixel.as_u8_slice();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            render_passes(
+                &build,
+                settings.samples_per_pixel,
+                restart_receiver,
+                tile_sender,
+            )
+        });
+
+        // Bumped on every restart so tiles of a stale accumulation get dropped
+        let mut generation = 0;
+        restart_sender.send((generation, diffuse_method)).unwrap();
+
+        // Pixels of the pass in flight have one sample more than the rest
+        let mut film = Film::new(settings.image_width, settings.image_height);
+        let mut finished_tiles = 0;
+        let mut start = Instant::now();
+        let mut redraw = false;
+
+        let display = |tone_mapper: &ToneMapper, film: &Film, index: usize| {
+            let c_u8 = tone_mapper.apply(film.pixel(index)).as_u8_slice();
             // ARGB but alpha ignored
-            *i = u32::from_be_bytes([0, c_u8[0], c_u8[1], c_u8[2]]);
-        }
+            u32::from_be_bytes([0, c_u8[0], c_u8[1], c_u8[2]])
+        };
+
+        while window.is_open() && !window.is_key_down(Key::Escape) {
+            for update in tile_receiver.try_iter() {
+                if update.generation != generation {
+                    continue;
+                }
+                film.merge(&update.film_tile);
+                for index in update.film_tile.bounds.indices(settings.image_width) {
+                    buffer[index] = display(&tone_mapper, &film, index);
+                }
+
+                finished_tiles += 1;
+                if finished_tiles % tile_count == 0 {
+                    let samples = finished_tiles / tile_count;
+                    window.set_title(&format!(
+                        "{}/{} samples - ESC to exit",
+                        samples, settings.samples_per_pixel
+                    ));
+                    if samples == settings.samples_per_pixel {
+                        eprintln!("Ray tracing took {:.3}s", start.elapsed().as_secs_f64());
+                    }
+                }
+            }
 
-        if window.is_key_down(Key::D) {
-            let next_diffuse_method = &mut diffuse_method_iter.next().unwrap();
-            pixels = trace(next_diffuse_method).unwrap();
-            eprintln!("Using {} diffuse method.", next_diffuse_method.to_string());
+            // Only the display transform changes, no need to trace again
+            if window.is_key_pressed(Key::T, KeyRepeat::No) {
+                tone_mapper.operator = operator_iter.next().unwrap();
+                eprintln!("Using {} tone mapping.", tone_mapper.operator);
+                redraw = true;
+            }
+
+            if window.is_key_pressed(Key::D, KeyRepeat::No) {
+                diffuse_method = diffuse_method_iter.next().unwrap();
+                eprintln!("Using {} diffuse method.", diffuse_method);
+                generation += 1;
+                restart_sender.send((generation, diffuse_method)).unwrap();
+                // The old image stays on screen until the new tiles replace it
+                film.clear();
+                finished_tiles = 0;
+                start = Instant::now();
+            }
+
+            if redraw {
+                for (index, pixel) in buffer.iter_mut().enumerate() {
+                    if film.has_samples(index) {
+                        *pixel = display(&tone_mapper, &film, index);
+                    }
+                }
+                redraw = false;
+            }
+
+            // We unwrap here as we want this code to exit if it fails. Real applications may want to handle this in a different way
+            window
+                .update_with_buffer(&buffer, settings.image_width, settings.image_height)
+                .unwrap();
         }
 
-        // We unwrap here as we want this code to exit if it fails. Real applications may want to handle this in a different way
-        window
-            .update_with_buffer(&buffer, IMAGE_WIDTH, IMAGE_HEIGHT)
-            .unwrap();
-    }
+        // Hanging up stops the render thread once its current pass is done
+        drop(restart_sender);
+    });
 
     Ok(())
 }
+
+/// Render thread, traces passes of one sample per pixel until the target is reached
+/// and then waits for a restart. Returns when the window hangs up.
+fn render_passes<F>(
+    build: &F,
+    target_samples: usize,
+    restarts: Receiver<(usize, DiffuseMethod)>,
+    tiles: Sender<TileUpdate>,
+) where
+    F: Fn(DiffuseMethod) -> Renderer,
+{
+    let mut next = restarts.recv().ok();
+    while let Some((generation, diffuse_method)) = next.take() {
+        let mut renderer = build(diffuse_method);
+        // Passes of one sample can't estimate a pixel's variance, the window
+        // always renders the target samples
+        renderer.settings.noise_threshold = None;
+        for sample in 0..target_samples {
+            // Only the latest of several quick restarts is worth rendering
+            loop {
+                match restarts.try_recv() {
+                    Ok(restart) => next = Some(restart),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            if next.is_some() {
+                break;
+            }
+
+            // A closed window is noticed through the restart channel after the pass
+            renderer.trace_tiles(sample..sample + 1, |film_tile| {
+                let _ = tiles.send(TileUpdate {
+                    generation,
+                    film_tile,
+                });
+            });
+        }
+        if next.is_none() {
+            next = restarts.recv().ok();
+        }
+    }
+}
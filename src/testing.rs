@@ -0,0 +1,37 @@
+//! Fixtures shared by the unit tests
+
+use std::sync::Arc;
+
+use crate::core::{
+    hit::Hittable,
+    material::Dielectric,
+    sphere::Sphere,
+    transform::Transform,
+    vec3::{Point3, Vec3},
+};
+use crate::render::tile::TileOrder;
+use crate::settings::RenderSettings;
+
+/// Glass sphere, for tests that only look at geometry
+pub fn sphere(center: Point3, radius: f64) -> Sphere {
+    Sphere::new(center, radius, Arc::new(Dielectric::new(1.5)))
+}
+
+pub fn unit_sphere() -> Arc<dyn Hittable> {
+    Arc::new(sphere(Point3::new(0., 0., 0.), 1.))
+}
+
+/// Stretched along x, turned upright about z and moved to z = -5
+pub fn placed() -> Transform {
+    Transform::scale(Vec3::new(2., 1., 1.))
+        .then(&Transform::rotate(Vec3::new(0., 0., 1.), 90.))
+        .then(&Transform::translate(Vec3::new(0., 0., -5.)))
+}
+
+/// One sample per pixel and a single bounce, in tiles of `tile_size` visited in `order`
+pub fn settings(width: usize, height: usize, tile_size: usize, order: TileOrder) -> RenderSettings {
+    let mut settings = RenderSettings::new(width, height, 1, 1);
+    settings.tile_size = tile_size;
+    settings.tile_order = order;
+    settings
+}
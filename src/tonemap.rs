@@ -0,0 +1,128 @@
+use enum_iterator::IntoEnumIterator;
+use strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr};
+
+use crate::core::color::Color;
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    EnumString,
+    EnumVariantNames,
+    IntoStaticStr,
+    IntoEnumIterator,
+    Display,
+)]
+#[strum(serialize_all = "kebab_case")]
+pub enum ToneMapOperator {
+    /// No curve, everything above 1 is clipped
+    Clamp,
+    Reinhard,
+    /// Reinhard that maps the white point to 1 instead of infinity
+    ExtendedReinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// Hable's filmic curve from Uncharted 2
+    Uncharted2,
+}
+
+/// Display transform from linear radiance to sRGB encoded values in [0, 1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ToneMapper {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops, each stop doubles the radiance
+    pub exposure: f64,
+    /// Smallest radiance mapped to pure white by the extended Reinhard operator
+    pub white_point: f64,
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMapOperator, exposure: f64, white_point: f64) -> ToneMapper {
+        ToneMapper {
+            operator,
+            exposure,
+            white_point,
+        }
+    }
+
+    /// Maps a linear color to a display-ready sRGB color
+    pub fn apply(&self, color: Color) -> Color {
+        let exposed = 2f64.powf(self.exposure) * color;
+        let curve = |c: f64| self.curve(c.max(0.));
+        Color::new(curve(exposed.r), curve(exposed.g), curve(exposed.b)).linear_to_srgb()
+    }
+
+    pub fn apply_all(&self, pixels: &[Color]) -> Vec<Color> {
+        pixels.iter().map(|pixel| self.apply(*pixel)).collect()
+    }
+
+    fn curve(&self, x: f64) -> f64 {
+        match self.operator {
+            ToneMapOperator::Clamp => x.min(1.),
+            ToneMapOperator::Reinhard => x / (1. + x),
+            ToneMapOperator::ExtendedReinhard => {
+                let white_sq = self.white_point * self.white_point;
+                (x * (1. + x / white_sq) / (1. + x)).min(1.)
+            }
+            ToneMapOperator::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0., 1.)
+            }
+            ToneMapOperator::Uncharted2 => {
+                const EXPOSURE_BIAS: f64 = 2.;
+                const WHITE: f64 = 11.2;
+                (uncharted2_partial(EXPOSURE_BIAS * x) / uncharted2_partial(WHITE)).min(1.)
+            }
+        }
+    }
+}
+
+fn uncharted2_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        ToneMapper::new(ToneMapOperator::Clamp, 0., 4.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_are_monotonic_from_black_to_white() {
+        for operator in ToneMapOperator::into_enum_iter() {
+            let mapper = ToneMapper::new(operator, 0., 4.);
+            assert!(mapper.curve(0.).abs() < 1e-12, "{}", operator);
+            assert!(mapper.apply(Color::new(0., 0., 0.)).r.abs() < 1e-12, "{}", operator);
+            let mut previous = mapper.curve(0.);
+            for i in 1..=1000 {
+                let value = mapper.curve(i as f64 * 0.05);
+                assert!(value >= previous, "{} decreases at {}", operator, i as f64 * 0.05);
+                assert!(value <= 1., "{} exceeds 1 at {}", operator, i as f64 * 0.05);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_the_white_point_to_white() {
+        for white_point in [1.5, 4., 10.] {
+            let mapper = ToneMapper::new(ToneMapOperator::ExtendedReinhard, 0., white_point);
+            assert!((mapper.curve(white_point) - 1.).abs() < 1e-12, "{}", white_point);
+            assert!(mapper.curve(0.5 * white_point) < 1.);
+        }
+    }
+
+    #[test]
+    fn exposure_scales_by_stops() {
+        let brighter = ToneMapper::new(ToneMapOperator::Reinhard, 1., 4.);
+        let plain = ToneMapper::new(ToneMapOperator::Reinhard, 0., 4.);
+        let color = Color::new(0.25, 0.5, 1.);
+        assert_eq!(brighter.apply(color), plain.apply(2. * color));
+    }
+}